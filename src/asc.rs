@@ -0,0 +1,159 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    errors::{Error, Result},
+    trace::TraceFrame,
+};
+
+const ASC_DATE_FORMAT: &str = "%a %b %d %I:%M:%S%.3f %P %Y";
+
+/// vector channels start at 1, the bus with id 0 is channel 1.
+pub fn bus_channel(bus_id: u32) -> u32 {
+    bus_id + 1
+}
+
+/// renders the frames as Vector ASC log, timestamps are relative to the timebase of the server,
+/// which starts at the UTC time start.
+pub fn write_asc(frames: &[TraceFrame], start: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    let date = start.format(ASC_DATE_FORMAT);
+    writeln!(out, "date {date}").unwrap();
+    writeln!(out, "base hex  timestamps absolute").unwrap();
+    writeln!(out, "internal events logged").unwrap();
+    writeln!(
+        out,
+        "// exported by canzero-cli {}",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    writeln!(out, "Begin Triggerblock {date}").unwrap();
+    writeln!(out, "   0.000000 Start of measurement").unwrap();
+    for frame in frames {
+        let id = if frame.ide {
+            format!("{:X}x", frame.id)
+        } else {
+            format!("{:X}", frame.id)
+        };
+        write!(
+            out,
+            "{:>11.6} {:<2} {id:<15} Rx   ",
+            frame.time().as_secs_f64(),
+            bus_channel(frame.bus)
+        )
+        .unwrap();
+        if frame.rtr {
+            writeln!(out, "r").unwrap();
+            continue;
+        }
+        write!(out, "d {:X}", frame.dlc).unwrap();
+        for byte in frame.data_bytes() {
+            write!(out, " {byte:02X}").unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    writeln!(out, "End TriggerBlock").unwrap();
+    out
+}
+
+/// parses a frame line of an ASC log, e.g. 1.234567 1  123x Rx   d 2 01 02
+fn parse_asc_line(line: &str, hex: bool) -> Option<TraceFrame> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let [time, channel, id, direction, kind, rest @ ..] = &parts[..] else {
+        return None;
+    };
+    if *direction != "Rx" && *direction != "Tx" {
+        return None;
+    }
+    let radix = if hex { 16 } else { 10 };
+    let (id, ide) = match id.strip_suffix('x') {
+        Some(id) => (id, true),
+        None => (*id, false),
+    };
+    let timestamp = (time.parse::<f64>().ok()? * 1e6).round() as u64;
+    let bus = channel.parse::<u32>().ok()?.checked_sub(1)?;
+    let id = u32::from_str_radix(id, radix).ok()?;
+    let (rtr, dlc, data) = match *kind {
+        "r" => (
+            true,
+            rest.first().and_then(|dlc| dlc.parse().ok()).unwrap_or(0),
+            0,
+        ),
+        "d" => {
+            let dlc = u8::from_str_radix(rest.first()?, 16).ok()?;
+            let mut data = [0u8; 8];
+            for (i, byte) in rest[1..].iter().take((dlc as usize).min(8)).enumerate() {
+                data[i] = u8::from_str_radix(byte, radix).ok()?;
+            }
+            (false, dlc, u64::from_le_bytes(data))
+        }
+        _ => return None,
+    };
+    Some(TraceFrame {
+        timestamp,
+        bus,
+        id,
+        ide,
+        rtr,
+        dlc,
+        data,
+    })
+}
+
+/// reads the can frames of an ASC log, all other events are ignored.
+pub fn read_asc(text: &str) -> Result<Vec<TraceFrame>> {
+    let mut hex = true;
+    let mut frames = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(base) = line.strip_prefix("base ") {
+            hex = base.trim_start().starts_with("hex");
+            continue;
+        }
+        if !line.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        // events like error frames, statistics or CAN FD frames are skipped,
+        // but a broken frame line is an error.
+        match parse_asc_line(line, hex) {
+            Some(frame) => frames.push(frame),
+            None if (line.contains(" Rx ") || line.contains(" Tx ")) && !line.contains("CANFD") => {
+                return Err(Error::InvalidTraceFile(format!("asc log : {line}")))
+            }
+            None => continue,
+        }
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::tests::sample_frames;
+
+    #[test]
+    fn round_trip() {
+        let text = write_asc(&sample_frames(), DateTime::<Utc>::UNIX_EPOCH);
+        assert!(text.starts_with("date Thu Jan 01 12:00:00.000 am 1970"));
+        assert_eq!(read_asc(&text).unwrap(), sample_frames());
+    }
+
+    #[test]
+    fn reads_decimal_ids() {
+        let text = "base dec  timestamps absolute\n   0.100000 2  291 Rx   d 1 10\n";
+        let frames = read_asc(text).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].bus, 1);
+        assert_eq!(frames[0].id, 291);
+        assert_eq!(frames[0].data, 10);
+    }
+
+    #[test]
+    fn skips_events_and_rejects_broken_frames() {
+        let text = "   0.100000 1  Statistic: D 0 R 0 XD 0 XR 0 E 0 O 0 B 0.00%\n\
+                    0.200000 1  CANFD 1 Rx 123 0 0 8 8 01 02 03 04 05 06 07 08\n";
+        assert!(read_asc(text).unwrap().is_empty());
+        assert!(read_asc("   0.100000 1  12G Rx   d 1 01\n").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+use crate::{asc::bus_channel, trace::TraceFrame};
+
+const FILE_HEADER_SIZE: usize = 144;
+const OBJECT_HEADER_BASE_SIZE: u16 = 16;
+const OBJECT_HEADER_V1_SIZE: u16 = 16;
+const CONTAINER_HEADER_SIZE: usize = 16;
+/// uncompressed size of the log containers.
+const MAX_CONTAINER_SIZE: usize = 128 * 1024;
+
+const OBJECT_TYPE_CAN_MESSAGE: u32 = 1;
+const OBJECT_TYPE_LOG_CONTAINER: u32 = 10;
+const OBJECT_FLAG_TIME_ONE_NANS: u32 = 2;
+const NO_COMPRESSION: u16 = 0;
+
+const CAN_MESSAGE_FLAG_RTR: u8 = 0x80;
+const CAN_ID_EXTENDED: u32 = 0x80000000;
+
+fn object_header_base(header_size: u16, object_size: u32, object_type: u32) -> Vec<u8> {
+    let mut header = b"LOBJ".to_vec();
+    header.extend_from_slice(&header_size.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // header version
+    header.extend_from_slice(&object_size.to_le_bytes());
+    header.extend_from_slice(&object_type.to_le_bytes());
+    header
+}
+
+fn can_message(frame: &TraceFrame) -> Vec<u8> {
+    let header_size = OBJECT_HEADER_BASE_SIZE + OBJECT_HEADER_V1_SIZE;
+    let mut object = object_header_base(
+        header_size,
+        header_size as u32 + 16,
+        OBJECT_TYPE_CAN_MESSAGE,
+    );
+    object.extend_from_slice(&OBJECT_FLAG_TIME_ONE_NANS.to_le_bytes());
+    object.extend_from_slice(&0u16.to_le_bytes()); // client index
+    object.extend_from_slice(&0u16.to_le_bytes()); // object version
+    object.extend_from_slice(&(frame.timestamp * 1000).to_le_bytes());
+
+    object.extend_from_slice(&(bus_channel(frame.bus) as u16).to_le_bytes());
+    object.push(if frame.rtr { CAN_MESSAGE_FLAG_RTR } else { 0 });
+    object.push(frame.dlc);
+    let id = if frame.ide {
+        frame.id | CAN_ID_EXTENDED
+    } else {
+        frame.id
+    };
+    object.extend_from_slice(&id.to_le_bytes());
+    object.extend_from_slice(&frame.data.to_le_bytes());
+    object
+}
+
+fn system_time(time: &DateTime<Utc>) -> Vec<u8> {
+    [
+        time.year() as u16,
+        time.month() as u16,
+        time.weekday().num_days_from_sunday() as u16,
+        time.day() as u16,
+        time.hour() as u16,
+        time.minute() as u16,
+        time.second() as u16,
+        (time.nanosecond() / 1_000_000).min(999) as u16,
+    ]
+    .iter()
+    .flat_map(|value| value.to_le_bytes())
+    .collect()
+}
+
+/// renders the frames as Vector BLF file with uncompressed log containers,
+/// timestamps are relative to the timebase of the server, which starts at the UTC time start.
+pub fn write_blf(frames: &[TraceFrame], start: DateTime<Utc>) -> Vec<u8> {
+    let mut out = vec![0; FILE_HEADER_SIZE];
+    let mut uncompressed_size = FILE_HEADER_SIZE as u64;
+
+    let objects: Vec<u8> = frames.iter().flat_map(can_message).collect();
+    for chunk in objects.chunks(MAX_CONTAINER_SIZE) {
+        let object_size =
+            (OBJECT_HEADER_BASE_SIZE as usize + CONTAINER_HEADER_SIZE + chunk.len()) as u32;
+        out.extend(object_header_base(
+            OBJECT_HEADER_BASE_SIZE,
+            object_size,
+            OBJECT_TYPE_LOG_CONTAINER,
+        ));
+        out.extend_from_slice(&NO_COMPRESSION.to_le_bytes());
+        out.extend_from_slice(&[0; 6]);
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(chunk);
+        while out.len() % 4 != 0 {
+            out.push(0);
+        }
+        uncompressed_size += object_size as u64;
+    }
+
+    let last = frames.last().map_or(start, |frame| {
+        start + chrono::Duration::microseconds(frame.timestamp as i64)
+    });
+    let mut header = b"LOGG".to_vec();
+    header.extend_from_slice(&(FILE_HEADER_SIZE as u32).to_le_bytes());
+    // application id, version and binlog version
+    header.extend_from_slice(&[0, 0, 0, 0, 2, 6, 8, 1]);
+    header.extend_from_slice(&(out.len() as u64).to_le_bytes());
+    header.extend_from_slice(&uncompressed_size.to_le_bytes());
+    header.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+    header.extend_from_slice(&0u32.to_le_bytes()); // objects read
+    header.extend(system_time(&start));
+    header.extend(system_time(&last));
+    out[..header.len()].copy_from_slice(&header);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::tests::sample_frames;
+
+    fn u32_at(buf: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// reads the can messages of the uncompressed log containers,
+    /// objects may span multiple containers.
+    fn read_can_messages(buf: &[u8]) -> Vec<TraceFrame> {
+        let mut objects = vec![];
+        let mut offset = FILE_HEADER_SIZE;
+        while offset < buf.len() {
+            assert_eq!(&buf[offset..offset + 4], b"LOBJ");
+            let object_size = u32_at(buf, offset + 8) as usize;
+            assert_eq!(u32_at(buf, offset + 12), OBJECT_TYPE_LOG_CONTAINER);
+            objects.extend_from_slice(&buf[offset + 32..offset + object_size]);
+            offset += object_size.div_ceil(4) * 4;
+        }
+        objects
+            .chunks(48)
+            .map(|object| {
+                assert_eq!(u32_at(object, 12), OBJECT_TYPE_CAN_MESSAGE);
+                let id = u32_at(object, 36);
+                TraceFrame {
+                    timestamp: u64::from_le_bytes(object[24..32].try_into().unwrap()) / 1000,
+                    bus: u16::from_le_bytes(object[32..34].try_into().unwrap()) as u32 - 1,
+                    id: id & !CAN_ID_EXTENDED,
+                    ide: id & CAN_ID_EXTENDED != 0,
+                    rtr: object[34] & CAN_MESSAGE_FLAG_RTR != 0,
+                    dlc: object[35],
+                    data: u64::from_le_bytes(object[40..48].try_into().unwrap()),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let blf = write_blf(&sample_frames(), DateTime::<Utc>::UNIX_EPOCH);
+        assert_eq!(read_can_messages(&blf), sample_frames());
+    }
+
+    #[test]
+    fn objects_span_containers() {
+        let frames: Vec<TraceFrame> = (0..5000)
+            .map(|i| TraceFrame {
+                timestamp: i * 1000,
+                bus: (i % 2) as u32,
+                id: i as u32 & 0x7FF,
+                ide: i % 3 == 0,
+                rtr: i % 7 == 0,
+                dlc: (i % 9) as u8,
+                data: i * 0x0101,
+            })
+            .collect();
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        let blf = write_blf(&frames, start);
+        assert_eq!(&blf[..4], b"LOGG");
+        assert_eq!(u32_at(&blf, 4) as usize, FILE_HEADER_SIZE);
+        assert_eq!(u32_at(&blf, 32) as usize, frames.len());
+        // year of the start time
+        assert_eq!(u16::from_le_bytes(blf[40..42].try_into().unwrap()), 1970);
+        assert_eq!(read_can_messages(&blf), frames);
+    }
+}
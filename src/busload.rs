@@ -0,0 +1,144 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use canzero_appdata::AppData;
+use canzero_config::config::{BusRef, MessageRef, MessageUsage, NetworkRef};
+use color_print::cprintln;
+
+use crate::{
+    config::{message_sender, network_config},
+    errors::{Error, Result},
+};
+
+/// worst-case length of a CAN 2.0 frame in bits, including
+/// bit stuffing and the interframe space.
+pub fn frame_bits(dlc: u8, extended: bool) -> u32 {
+    let data_bits = dlc as u32 * 8;
+    // bits of the frame which are subject to bit stuffing (SOF to CRC).
+    let (fixed_bits, stuffable_bits) = if extended {
+        (67, 54 + data_bits)
+    } else {
+        (47, 34 + data_bits)
+    };
+    fixed_bits + data_bits + (stuffable_bits - 1) / 4
+}
+
+/// the shortest interval in which the message is sent.
+/// returns None for messages, which are only sent on request.
+pub fn message_interval(msg: &MessageRef) -> Option<Duration> {
+    match msg.usage() {
+        MessageUsage::Stream(stream) => Some(*stream.min_interval()),
+        MessageUsage::External { interval } => Some(*interval),
+        _ => None,
+    }
+}
+
+/// the longest interval in which the message is guaranteed to be sent,
+/// streams send more often if their value changes.
+/// returns None for messages, which are only sent on request.
+pub fn message_period(msg: &MessageRef) -> Option<Duration> {
+    match msg.usage() {
+        MessageUsage::Stream(stream) => Some(*stream.max_interval()),
+        MessageUsage::External { interval } => Some(*interval),
+        _ => None,
+    }
+}
+
+/// the fraction of the bus bandwidth occupied by msg.
+pub fn message_load(msg: &MessageRef) -> Option<f64> {
+    let interval = message_interval(msg)?;
+    if interval.is_zero() {
+        return None;
+    }
+    let bits = frame_bits(msg.dlc(), msg.id().ide()) as f64;
+    let baudrate = msg.bus().baudrate() as f64;
+    Some(bits / (interval.as_secs_f64() * baudrate))
+}
+
+pub fn bus_load(network: &NetworkRef, bus: &BusRef) -> f64 {
+    network
+        .messages()
+        .iter()
+        .filter(|m| m.bus().id() == bus.id())
+        .filter_map(message_load)
+        .sum()
+}
+
+pub fn print_load(label: &str, load: f64) {
+    let percent = load * 100.0;
+    if load > 1.0 {
+        cprintln!("{label} <red>{percent:6.2}%</red>");
+    } else if load > 0.7 {
+        cprintln!("{label} <yellow>{percent:6.2}%</yellow>");
+    } else {
+        cprintln!("{label} <green>{percent:6.2}%</green>");
+    }
+}
+
+pub fn command_config_busload(filter_bus: Option<String>) -> Result<()> {
+    let appdata = AppData::read()?;
+    let network = network_config(&appdata)?;
+
+    if let Some(bus_name) = &filter_bus {
+        if !network.buses().iter().any(|b| b.name() == bus_name) {
+            return Err(Error::InvalidBusName(bus_name.clone()));
+        }
+    };
+
+    for bus in network.buses() {
+        if let Some(bus_name) = &filter_bus {
+            if bus.name() != bus_name {
+                continue;
+            }
+        }
+        let mut messages: Vec<(&MessageRef, f64)> = network
+            .messages()
+            .iter()
+            .filter(|m| m.bus().id() == bus.id())
+            .filter_map(|m| Some((m, message_load(m)?)))
+            .collect();
+        messages.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        let on_request = network
+            .messages()
+            .iter()
+            .filter(|m| m.bus().id() == bus.id())
+            .count()
+            - messages.len();
+
+        let mut nodes: BTreeMap<String, f64> = BTreeMap::new();
+        for (msg, load) in &messages {
+            let sender =
+                message_sender(&network, msg).map_or("???".to_owned(), |n| n.name().to_owned());
+            *nodes.entry(sender).or_default() += load;
+        }
+        let mut nodes: Vec<(String, f64)> = nodes.into_iter().collect();
+        nodes.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        print_load(
+            &format!("BUS {} ({} baud) :", bus.name(), bus.baudrate()),
+            bus_load(&network, bus),
+        );
+        println!("  NODE");
+        for (node, load) in nodes {
+            print_load(&format!("  {node:25}"), load);
+        }
+        println!("  ID     DLC  BITS  INTERVAL   NAME");
+        for (msg, load) in messages {
+            let id = msg.id();
+            let dlc = msg.dlc();
+            let bits = frame_bits(dlc, id.ide());
+            let interval = message_interval(msg).unwrap();
+            print_load(
+                &format!(
+                    "  {id:5} [{dlc:1}]  {bits:4}  {:8.2}ms  {:25}",
+                    interval.as_secs_f64() * 1000.0,
+                    msg.name()
+                ),
+                load,
+            );
+        }
+        if on_request != 0 {
+            println!("  + {on_request} messages, which are only sent on request");
+        }
+    }
+    Ok(())
+}
@@ -1,13 +1,89 @@
 use std::{
     cmp::Ordering,
-    path::PathBuf,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
 };
 
 use canzero_appdata::AppData;
-use canzero_config::config::{self, Type};
+use canzero_config::config::{self, MessageRef, NetworkRef, NodeRef, Type};
 
-use crate::errors::{Error, Result};
+use crate::{
+    config_repo::{command_config_set_repo, is_git_url, ConfigSource},
+    errors::{Error, Result},
+    profiles::{resolve_profile_or_path, Profile},
+};
+
+/// config given by the global --config option,
+/// which takes precedence over the config selected in the AppData.
+static CONFIG_OVERRIDE: OnceLock<Profile> = OnceLock::new();
+
+pub fn set_config_override(name_or_path: &str) -> Result<()> {
+    let profile = resolve_profile_or_path(name_or_path)?;
+    CONFIG_OVERRIDE
+        .set(profile)
+        .expect("config override set multiple times");
+    Ok(())
+}
+
+pub fn config_path(appdata: &AppData) -> Option<PathBuf> {
+    match CONFIG_OVERRIDE.get() {
+        Some(profile) => Some(profile.path.clone()),
+        None => appdata.get_config_path().map(|p| p.to_path_buf()),
+    }
+}
+
+/// the git repository of the config returned by config_path.
+pub fn config_source() -> Result<Option<ConfigSource>> {
+    match CONFIG_OVERRIDE.get() {
+        Some(profile) => Ok(profile.source.clone()),
+        None => ConfigSource::read(),
+    }
+}
+
+pub fn network_config(appdata: &AppData) -> Result<NetworkRef> {
+    match CONFIG_OVERRIDE.get() {
+        Some(profile) => load_network_config(&profile.path),
+        None => Ok(appdata.config()?),
+    }
+}
+
+/// all files of the network config, including the main config file.
+pub fn config_files(appdata: &AppData) -> Result<Vec<PathBuf>> {
+    let Some(config_path) = config_path(appdata) else {
+        return Err(Error::NoConfigSelected);
+    };
+    if CONFIG_OVERRIDE.get().is_some() {
+        let config_dir = config_path.parent().unwrap();
+        let mut config_files = vec![];
+        for entry in std::fs::read_dir(config_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml") {
+                config_files.push(path);
+            }
+        }
+        return Ok(config_files);
+    }
+    let mut config_files = appdata.config_files()?;
+    if !config_files.contains(&config_path) {
+        config_files.push(config_path);
+    }
+    Ok(config_files)
+}
+
+/// the directory, which contains all files of the network config.
+pub fn config_dir(appdata: &AppData) -> Result<PathBuf> {
+    let Some(config_path) = config_path(appdata) else {
+        return Err(Error::NoConfigSelected);
+    };
+    if CONFIG_OVERRIDE.get().is_some() {
+        // assumes that the main config file is in the common directory
+        return Ok(config_path.parent().unwrap().to_path_buf());
+    }
+    let config_files = config_files(appdata)?;
+    let config_dir = common_path::common_path_all(config_files.iter().map(|p| p.as_path()))
+        .expect("Failed to find common network-config directory");
+    Ok(config_dir)
+}
 
 pub fn command_config_show() -> Result<()> {
     Err(Error::NotYetImplemented)
@@ -15,77 +91,130 @@ pub fn command_config_show() -> Result<()> {
 
 pub fn command_config_nodes_list() -> Result<()> {
     let appdata = AppData::read()?;
-    let network = appdata.config()?;
+    let network = network_config(&appdata)?;
     for node in network.nodes() {
         println!("{:15} {:3}", node.name(), node.id());
     }
     Ok(())
 }
 
+pub fn type_name(ty: &Type) -> String {
+    match ty {
+        config::Type::Primitive(prim) => match prim {
+            config::SignalType::UnsignedInt { size } => format!("u{size}"),
+            config::SignalType::SignedInt { size } => format!("i{size}"),
+            config::SignalType::Decimal {
+                size,
+                offset,
+                scale,
+            } => {
+                let min = *offset;
+                let max = (2u128.pow(*size as u32) as f64 / *scale) + min;
+                format!("d{size}<{min}..{max}> (scale = {scale})")
+            }
+        },
+        config::Type::Struct {
+            name,
+            description: _,
+            attribs: _,
+            visibility: _,
+        } => format!("{name}"),
+        config::Type::Enum {
+            name,
+            description: _,
+            size: _,
+            entries: _,
+            visibility: _,
+        } => format!("{name}"),
+        config::Type::Array { len, ty } => {
+            format!("{}[{len}]", type_name(ty))
+        }
+    }
+}
+
+pub fn message_sender(network: &NetworkRef, msg: &MessageRef) -> Option<NodeRef> {
+    network
+        .nodes()
+        .iter()
+        .find(|n| n.tx_messages().iter().any(|m| m.name() == msg.name()))
+        .map(Arc::clone)
+}
+
+pub fn message_receivers(network: &NetworkRef, msg: &MessageRef) -> Vec<NodeRef> {
+    network
+        .nodes()
+        .iter()
+        .filter(|n| n.rx_messages().iter().any(|m| m.name() == msg.name()))
+        .map(Arc::clone)
+        .collect()
+}
+
+/// the message of a frame, a message configured on the bus of the frame takes precedence
+/// over messages with the same id on other buses.
+pub fn frame_message(network: &NetworkRef, bus: u32, id: u32, ide: bool) -> Option<MessageRef> {
+    let mut messages = network
+        .messages()
+        .iter()
+        .filter(|msg| msg.id().as_u32() == id && msg.id().ide() == ide);
+    let first = messages.next()?;
+    if first.bus().id() == bus {
+        return Some(first.clone());
+    }
+    Some(
+        messages
+            .find(|msg| msg.bus().id() == bus)
+            .unwrap_or(first)
+            .clone(),
+    )
+}
+
+pub fn load_network_config(path: &Path) -> Result<NetworkRef> {
+    if !path.exists() {
+        return Err(Error::FileNotFound(path.to_str().unwrap().to_owned()));
+    }
+    canzero_yaml::parse_yaml_config_from_file(path.to_str().unwrap())
+        .map_err(|err| Error::InvalidConfig(format!("{err:?}")))
+}
+
 pub fn command_config_object_entries_list(node: String) -> Result<()> {
     let appdata = AppData::read()?;
-    let network = appdata.config()?;
+    let network = network_config(&appdata)?;
     let Some(node) = network.nodes().iter().find(|n| n.name() == node) else {
         return Err(Error::InvalidNodeName(node));
     };
     for oe in node.object_entries() {
-        fn ty_to_name(ty: &Type) -> String {
-            match ty {
-                config::Type::Primitive(prim) => match prim {
-                    config::SignalType::UnsignedInt { size } => format!("u{size}"),
-                    config::SignalType::SignedInt { size } => format!("i{size}"),
-                    config::SignalType::Decimal {
-                        size,
-                        offset,
-                        scale,
-                    } => {
-                        let min = *offset;
-                        let max = (2u128.pow(*size as u32) as f64 / *scale) + min;
-                        format!("d{size}<{min}..{max}> (scale = {scale})")
-                    }
-                },
-                config::Type::Struct {
-                    name,
-                    description: _,
-                    attribs: _,
-                    visibility: _,
-                } => format!("{name}"),
-                config::Type::Enum {
-                    name,
-                    description: _,
-                    size: _,
-                    entries: _,
-                    visibility: _,
-                } => format!("{name}"),
-                config::Type::Array { len, ty } => {
-                    format!("{}[{len}", ty_to_name(ty))
-                }
-            }
-        }
-        println!("[{}] = {} : {}", oe.id(), oe.name(), ty_to_name(oe.ty()));
+        println!("[{}] = {} : {}", oe.id(), oe.name(), type_name(oe.ty()));
     }
 
     Ok(())
 }
 
-pub fn command_config_set(path: PathBuf) -> Result<()> {
+pub fn command_config_set(path: String, rev: Option<String>, file: Option<PathBuf>) -> Result<()> {
+    if is_git_url(&path) {
+        return command_config_set_repo(path, rev, file);
+    }
     let mut appdata = AppData::read()?;
-    appdata.set_config_path(Some(path))?;
+    appdata.set_config_path(Some(PathBuf::from(path)))?;
+    ConfigSource::clear()?;
     Ok(())
 }
 
 pub fn command_config_get() -> Result<()> {
     let appdata = AppData::read()?;
-    match appdata.get_config_path() {
+    match config_path(&appdata) {
         Some(path) => println!("{path:?}"),
         None => println!("No path to config specificied"),
     }
+    if let Some(source) = config_source()? {
+        let pinned = if source.pinned { " (pinned)" } else { "" };
+        println!("from {} at {} ({}){pinned}", source.url, source.rev, source.commit);
+    }
     Ok(())
 }
 
 pub fn command_config_messages_list(node: Option<String>, bus: Option<String>) -> Result<()> {
     let appdata = AppData::read()?;
-    let network = appdata.config()?;
+    let network = network_config(&appdata)?;
 
     if let Some(bus_name) = &bus {
         if !network.buses().iter().any(|b| b.name() == bus_name) {
@@ -156,15 +285,9 @@ pub fn command_config_messages_list(node: Option<String>, bus: Option<String>) -
     Ok(())
 }
 
-pub fn command_config_check() -> Result<()> {
-    let appdata = AppData::read()?;
-    let _ = appdata.config()?;
-    Ok(())
-}
-
 pub fn command_config_hash() -> Result<()> {
     let appdata = AppData::read()?;
-    let network = appdata.config()?;
+    let network = network_config(&appdata)?;
 
     println!("{}", network.portable_hash());
     Ok(())
@@ -0,0 +1,265 @@
+use std::path::{Path, PathBuf};
+
+use canzero_appdata::AppData;
+use color_print::cprintln;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{Error, Result},
+    git::git,
+};
+
+const CONFIG_REPOS_PATH: &'static str = "network-configs";
+const CONFIG_SOURCE_FILE: &'static str = "config-source.yaml";
+
+/// describes from which git repository the selected config was taken.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigSource {
+    pub url: String,
+    /// the requested branch, tag or commit.
+    pub rev: String,
+    /// the commit, which rev resolved to when it was last updated.
+    pub commit: String,
+    /// path of the main config file relative to the repository root.
+    pub file: PathBuf,
+    pub pinned: bool,
+}
+
+impl ConfigSource {
+    fn path() -> PathBuf {
+        let mut path = AppData::dir();
+        path.push(CONFIG_SOURCE_FILE);
+        path
+    }
+
+    pub fn read() -> Result<Option<ConfigSource>> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)?;
+        match serde_yaml::from_str(&content) {
+            Ok(source) => Ok(Some(source)),
+            Err(err) => Err(Error::InvalidConfig(format!("{err:?}"))),
+        }
+    }
+
+    pub fn write(&self) -> Result<()> {
+        let content =
+            serde_yaml::to_string(self).map_err(|err| Error::InvalidConfig(format!("{err:?}")))?;
+        std::fs::write(Self::path(), content)?;
+        Ok(())
+    }
+
+    /// forgets about the source, used when a local config is selected.
+    pub fn clear() -> Result<()> {
+        let path = Self::path();
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn repo_dir(&self) -> PathBuf {
+        repo_dir(&self.url)
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.repo_dir().join(&self.file)
+    }
+}
+
+pub fn is_git_url(path: &str) -> bool {
+    path.starts_with("https://")
+        || path.starts_with("http://")
+        || path.starts_with("ssh://")
+        || path.starts_with("git@")
+        || path.ends_with(".git")
+}
+
+/// 64 bit FNV-1a, which unlike the std hasher is stable across rust versions.
+fn url_hash(url: &str) -> u64 {
+    url.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// repositories are cloned to <name>-<hash of url>, such that
+/// repositories with the same name but different urls don't share a checkout.
+fn repo_dir(url: &str) -> PathBuf {
+    let name = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or(url)
+        .trim_end_matches(".git");
+    let mut path = AppData::dir();
+    path.push(CONFIG_REPOS_PATH);
+    path.push(format!("{name}-{:016x}", url_hash(url)));
+    path
+}
+
+/// resolves rev to a commit, branches are resolved against the remote.
+fn resolve_rev(repo_dir: &Path, rev: &str) -> Result<String> {
+    if let Ok(commit) = git(
+        repo_dir,
+        ["rev-parse", "--verify", &format!("origin/{rev}^{{commit}}")],
+    ) {
+        return Ok(commit);
+    }
+    git(
+        repo_dir,
+        ["rev-parse", "--verify", &format!("{rev}^{{commit}}")],
+    )
+}
+
+fn fetch(repo_dir: &Path, url: &str) -> Result<()> {
+    if repo_dir.exists() {
+        println!("Fetching {url}");
+        git(repo_dir, ["fetch", "--tags", "--force", "origin"])?;
+    } else {
+        println!("Cloning {url}");
+        let parent = repo_dir.parent().unwrap();
+        std::fs::create_dir_all(parent)?;
+        git(parent, ["clone", url, repo_dir.to_str().unwrap()])?;
+    }
+    Ok(())
+}
+
+/// searches for the main config file, if it is not given explicitly.
+fn find_config_file(repo_dir: &Path) -> Result<PathBuf> {
+    let yaml_files: Vec<PathBuf> = std::fs::read_dir(repo_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect();
+    match yaml_files.as_slice() {
+        [file] => Ok(PathBuf::from(file.file_name().unwrap())),
+        _ => Err(Error::FileNotFound(format!(
+            "{}/<config>.yaml (use --file to select the main config file)",
+            repo_dir.to_str().unwrap()
+        ))),
+    }
+}
+
+fn checkout(source: &mut ConfigSource) -> Result<()> {
+    let repo_dir = source.repo_dir();
+    let commit = resolve_rev(&repo_dir, &source.rev)?;
+    git(&repo_dir, ["checkout", "--detach", "--force", &commit])?;
+    source.commit = commit;
+    Ok(())
+}
+
+fn select(source: &ConfigSource) -> Result<()> {
+    let config_path = source.config_path();
+    if !config_path.exists() {
+        return Err(Error::FileNotFound(
+            config_path.to_str().unwrap().to_owned(),
+        ));
+    }
+    let mut appdata = AppData::read()?;
+    appdata.set_config_path(Some(config_path))?;
+    source.write()?;
+    Ok(())
+}
+
+/// checks out the commit of a previously selected source, e.g. of a profile.
+pub fn check_out(source: &ConfigSource) -> Result<()> {
+    let repo_dir = source.repo_dir();
+    let commit = format!("{}^{{commit}}", source.commit);
+    if !repo_dir.exists() || git(&repo_dir, ["cat-file", "-e", &commit]).is_err() {
+        fetch(&repo_dir, &source.url)?;
+    }
+    git(
+        &repo_dir,
+        ["checkout", "--detach", "--force", &source.commit],
+    )?;
+    Ok(())
+}
+
+/// checks out the commit of a previously selected source and selects its config.
+pub fn restore(source: &ConfigSource) -> Result<()> {
+    check_out(source)?;
+    select(source)
+}
+
+pub fn command_config_set_repo(
+    url: String,
+    rev: Option<String>,
+    file: Option<PathBuf>,
+) -> Result<()> {
+    let repo_dir = repo_dir(&url);
+    fetch(&repo_dir, &url)?;
+
+    let rev = rev.unwrap_or("HEAD".to_owned());
+    let mut source = ConfigSource {
+        url,
+        rev: rev.clone(),
+        commit: String::new(),
+        file: PathBuf::new(),
+        pinned: false,
+    };
+    checkout(&mut source)?;
+    source.file = match file {
+        Some(file) => file,
+        None => find_config_file(&repo_dir)?,
+    };
+    select(&source)?;
+    cprintln!(
+        "<green>Selected {} at {} ({})</green>",
+        source.url,
+        source.rev,
+        source.commit
+    );
+    Ok(())
+}
+
+pub fn command_config_pull() -> Result<()> {
+    let Some(mut source) = ConfigSource::read()? else {
+        println!("The selected config is not part of a git repository");
+        return Ok(());
+    };
+    if source.pinned {
+        cprintln!(
+            "<yellow>Config is pinned to {}, not updating</yellow>",
+            source.commit
+        );
+        return Ok(());
+    }
+    fetch(&source.repo_dir(), &source.url)?;
+    let previous_commit = source.commit.clone();
+    checkout(&mut source)?;
+    select(&source)?;
+    if previous_commit == source.commit {
+        println!("Config is up to date ({})", source.commit);
+    } else {
+        cprintln!(
+            "<green>Updated config {} -> {}</green>",
+            previous_commit,
+            source.commit
+        );
+    }
+    Ok(())
+}
+
+pub fn command_config_pin(rev: Option<String>) -> Result<()> {
+    let Some(mut source) = ConfigSource::read()? else {
+        println!("The selected config is not part of a git repository");
+        return Ok(());
+    };
+    if let Some(rev) = rev {
+        fetch(&source.repo_dir(), &source.url)?;
+        source.rev = rev;
+        checkout(&mut source)?;
+    }
+    source.rev = source.commit.clone();
+    source.pinned = true;
+    select(&source)?;
+    cprintln!("<green>Pinned config to {}</green>", source.commit);
+    Ok(())
+}
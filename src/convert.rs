@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use canzero_appdata::AppData;
+
+use crate::{
+    config::network_config,
+    errors::{Error, Result},
+    trace::{bus_names, read_trace, write_trace, TraceFormat},
+};
+
+pub fn command_convert(input: PathBuf, output: PathBuf, format: Option<TraceFormat>) -> Result<()> {
+    let Some(format) = format.or_else(|| TraceFormat::from_path(&output)) else {
+        return Err(Error::UnsupportedFileFormat(format!("{output:?}")));
+    };
+    // bus names are taken from the network config, converting works without one.
+    let buses = AppData::read()
+        .ok()
+        .and_then(|appdata| network_config(&appdata).ok())
+        .map_or(vec![], |network| bus_names(&network));
+
+    let trace = read_trace(&input, &buses)?;
+    write_trace(&output, format, &trace, &buses)?;
+    println!("Converted {} frames to {output:?}", trace.frames.len());
+    Ok(())
+}
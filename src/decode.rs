@@ -0,0 +1,141 @@
+use canzero_config::config::{
+    message::TypeSignalEncoding, MessageRef, SignalRef, SignalType, Type, TypeRef,
+};
+
+/// the raw bits of signal in the (little endian) frame data.
+pub fn signal_raw(data: u64, signal: &SignalRef) -> u64 {
+    let size = signal.size() as u32;
+    let mask = 0xFFFFFFFFFFFFFFFFu64.overflowing_shr(64 - size).0;
+    data.overflowing_shr(signal.byte_offset() as u32).0 & mask
+}
+
+/// converts the raw bits of signal into its physical value.
+pub fn signal_value(data: u64, signal: &SignalRef) -> f64 {
+    let raw = signal_raw(data, signal);
+    match signal.ty() {
+        SignalType::UnsignedInt { .. } => raw as f64,
+        SignalType::SignedInt { size } => {
+            // sign extend
+            let shift = 64 - *size as u32;
+            (raw.overflowing_shl(shift).0 as i64)
+                .overflowing_shr(shift)
+                .0 as f64
+        }
+        SignalType::Decimal { offset, scale, .. } => raw as f64 * scale + offset,
+    }
+}
+
+/// converts a physical value into the raw bits of signal, placed at its offset.
+pub fn encode_signal(value: f64, signal: &SignalRef) -> u64 {
+    let size = signal.size() as u32;
+    let mask = 0xFFFFFFFFFFFFFFFFu64.overflowing_shr(64 - size).0;
+    let raw = match signal.ty() {
+        SignalType::UnsignedInt { .. } => value as u64,
+        SignalType::SignedInt { .. } => value as i64 as u64,
+        SignalType::Decimal { offset, scale, .. } => ((value - offset) / scale).round() as u64,
+    };
+    (raw & mask).overflowing_shl(signal.byte_offset() as u32).0
+}
+
+/// the data of a frame masked to its dlc.
+pub fn frame_data(data: u64, dlc: u8) -> u64 {
+    let mask = 0xFFFFFFFFFFFFFFFFu64
+        .overflowing_shr(64u32 - dlc as u32 * 8u32)
+        .0;
+    data & mask
+}
+
+fn find_encoding<'a>(
+    encodings: &'a [TypeSignalEncoding],
+    signal: &SignalRef,
+) -> Option<&'a TypeSignalEncoding> {
+    for encoding in encodings {
+        match encoding {
+            TypeSignalEncoding::Composite(composite) => {
+                if let Some(found) = find_encoding(composite.attributes(), signal) {
+                    return Some(found);
+                }
+            }
+            TypeSignalEncoding::Primitive(primitive) => {
+                if primitive.signal().name() == signal.name() {
+                    return Some(encoding);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// the enum type of a signal, if the signal encodes an enum.
+pub fn signal_enum(msg: &MessageRef, signal: &SignalRef) -> Option<TypeRef> {
+    let encoding = msg.encoding()?;
+    match find_encoding(encoding.attributes(), signal)? {
+        TypeSignalEncoding::Primitive(primitive) => match &**primitive.ty() {
+            Type::Enum { .. } => Some(primitive.ty().clone()),
+            _ => None,
+        },
+        TypeSignalEncoding::Composite(_) => None,
+    }
+}
+
+/// the name of the enum entry with the given value.
+pub fn enum_entry_name(ty: &Type, value: u64) -> Option<&str> {
+    match ty {
+        Type::Enum { entries, .. } => entries
+            .iter()
+            .find(|(_, entry_value)| *entry_value == value)
+            .map(|(name, _)| name.as_str()),
+        _ => None,
+    }
+}
+
+/// decodes all signals of msg into their physical value.
+pub fn decode_message(msg: &MessageRef, data: u64) -> Vec<(SignalRef, f64)> {
+    msg.signals()
+        .iter()
+        .map(|signal| (signal.clone(), signal_value(data, signal)))
+        .collect()
+}
+
+fn flatten_into(
+    encoding: &TypeSignalEncoding,
+    prefix: Option<&str>,
+    out: &mut Vec<(String, SignalRef)>,
+) {
+    let (name, signal) = match encoding {
+        TypeSignalEncoding::Composite(composite) => {
+            let name = match prefix {
+                Some(prefix) => format!("{prefix}.{}", composite.name()),
+                None => composite.name().to_owned(),
+            };
+            for attribute in composite.attributes() {
+                flatten_into(attribute, Some(&name), out);
+            }
+            return;
+        }
+        TypeSignalEncoding::Primitive(primitive) => (primitive.name(), primitive.signal()),
+    };
+    let name = match prefix {
+        Some(prefix) => format!("{prefix}.{name}"),
+        None => name.to_owned(),
+    };
+    out.push((name, signal.clone()));
+}
+
+/// flattens the encoding of an object entry into its primitive signals.
+/// struct attributes are named relative to the object entry and joined with dots
+/// (e.g. foo.x), a primitive object entry has a single signal named value.
+pub fn flatten_object_entry(encoding: &TypeSignalEncoding) -> Vec<(String, SignalRef)> {
+    let mut out = vec![];
+    match encoding {
+        TypeSignalEncoding::Composite(composite) => {
+            for attribute in composite.attributes() {
+                flatten_into(attribute, None, &mut out);
+            }
+        }
+        TypeSignalEncoding::Primitive(primitive) => {
+            out.push(("value".to_owned(), primitive.signal().clone()))
+        }
+    }
+    out
+}
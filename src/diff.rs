@@ -0,0 +1,433 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use canzero_appdata::AppData;
+use canzero_config::config::{MessageRef, NetworkRef, Type};
+use color_print::cprintln;
+
+use crate::{
+    config::{config_path, load_network_config, network_config, type_name},
+    errors::{Error, Result},
+    git::{git, toplevel},
+};
+
+/// parent directory of the worktrees of the compared revisions.
+const DIFF_WORKTREE_DIR: &'static str = "diff-worktrees";
+
+enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+struct DiffEntry {
+    kind: DiffKind,
+    name: String,
+    detail: String,
+    breaking: bool,
+}
+
+impl DiffEntry {
+    fn added(name: impl Into<String>) -> Self {
+        DiffEntry {
+            kind: DiffKind::Added,
+            name: name.into(),
+            detail: String::new(),
+            breaking: false,
+        }
+    }
+    fn removed(name: impl Into<String>) -> Self {
+        DiffEntry {
+            kind: DiffKind::Removed,
+            name: name.into(),
+            detail: String::new(),
+            breaking: false,
+        }
+    }
+    fn changed(name: impl Into<String>, detail: String) -> Self {
+        DiffEntry {
+            kind: DiffKind::Changed,
+            name: name.into(),
+            detail,
+            breaking: false,
+        }
+    }
+    fn breaking(mut self, breaking: bool) -> Self {
+        self.breaking = breaking;
+        self
+    }
+}
+
+/// everything of a message, which ends up on the wire.
+fn message_encoding(msg: &MessageRef) -> (String, String, u8, BTreeMap<String, String>) {
+    (
+        msg.id().to_string(),
+        msg.bus().name().to_owned(),
+        msg.dlc(),
+        signal_layout(msg),
+    )
+}
+
+/// expands structs and enums, such that layout changes
+/// of a type are visible and not only its name.
+fn type_layout(ty: &Type) -> String {
+    match ty {
+        Type::Primitive(_) => type_name(ty),
+        Type::Struct { name, attribs, .. } => {
+            let attribs = attribs
+                .iter()
+                .map(|(attrib_name, attrib_ty)| format!("{attrib_name}:{}", type_layout(attrib_ty)))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("{name}{{{attribs}}}")
+        }
+        Type::Enum {
+            name,
+            size,
+            entries,
+            ..
+        } => {
+            let entries = entries
+                .iter()
+                .map(|(entry_name, value)| format!("{entry_name}={value}"))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("{name}<{size}>{{{entries}}}")
+        }
+        Type::Array { len, ty } => format!("{}[{len}]", type_layout(ty)),
+    }
+}
+
+fn signal_layout(msg: &MessageRef) -> BTreeMap<String, String> {
+    msg.signals()
+        .iter()
+        .map(|s| {
+            (
+                s.name().to_owned(),
+                format!("@{} {}bit {:?}", s.byte_offset(), s.size(), s.ty()),
+            )
+        })
+        .collect()
+}
+
+/// added or removed nodes, messages and object entries are breaking
+/// if they change the portable hash of the network.
+fn diff_nodes(old: &NetworkRef, new: &NetworkRef, hash_changed: bool) -> Vec<DiffEntry> {
+    let old_nodes: BTreeMap<_, _> = old.nodes().iter().map(|n| (n.name(), n)).collect();
+    let new_nodes: BTreeMap<_, _> = new.nodes().iter().map(|n| (n.name(), n)).collect();
+    let mut entries = vec![];
+    for (name, node) in &old_nodes {
+        match new_nodes.get(name) {
+            None => entries.push(DiffEntry::removed(*name).breaking(hash_changed)),
+            Some(new_node) => {
+                if node.id() != new_node.id() {
+                    entries.push(
+                        DiffEntry::changed(*name, format!("id {} -> {}", node.id(), new_node.id()))
+                            .breaking(hash_changed),
+                    );
+                }
+            }
+        }
+    }
+    for name in new_nodes.keys() {
+        if !old_nodes.contains_key(name) {
+            entries.push(DiffEntry::added(*name).breaking(hash_changed));
+        }
+    }
+    entries
+}
+
+fn diff_messages(
+    old: &NetworkRef,
+    new: &NetworkRef,
+    hash_changed: bool,
+) -> (Vec<DiffEntry>, Vec<DiffEntry>) {
+    let old_messages: BTreeMap<_, _> = old.messages().iter().map(|m| (m.name(), m)).collect();
+    let new_messages: BTreeMap<_, _> = new.messages().iter().map(|m| (m.name(), m)).collect();
+    let mut message_entries = vec![];
+    let mut signal_entries = vec![];
+    for (name, msg) in &old_messages {
+        let Some(new_msg) = new_messages.get(name) else {
+            message_entries.push(DiffEntry::removed(*name).breaking(hash_changed));
+            continue;
+        };
+        let encoding_changed = message_encoding(msg) != message_encoding(new_msg);
+        if msg.id() != new_msg.id() {
+            message_entries.push(
+                DiffEntry::changed(*name, format!("id {} -> {}", msg.id(), new_msg.id()))
+                    .breaking(encoding_changed),
+            );
+        }
+        if msg.bus().name() != new_msg.bus().name() {
+            message_entries.push(
+                DiffEntry::changed(
+                    *name,
+                    format!("bus {} -> {}", msg.bus().name(), new_msg.bus().name()),
+                )
+                .breaking(encoding_changed),
+            );
+        }
+        if msg.dlc() != new_msg.dlc() {
+            message_entries.push(
+                DiffEntry::changed(*name, format!("dlc {} -> {}", msg.dlc(), new_msg.dlc()))
+                    .breaking(encoding_changed),
+            );
+        }
+        if msg.description() != new_msg.description() {
+            // descriptions are not part of the encoding.
+            message_entries.push(DiffEntry::changed(
+                *name,
+                format!(
+                    "description {:?} -> {:?}",
+                    msg.description(),
+                    new_msg.description()
+                ),
+            ));
+        }
+        let old_signals = signal_layout(msg);
+        let new_signals = signal_layout(new_msg);
+        for (signal_name, layout) in &old_signals {
+            match new_signals.get(signal_name) {
+                None => signal_entries.push(
+                    DiffEntry::removed(format!("{name}.{signal_name}")).breaking(encoding_changed),
+                ),
+                Some(new_layout) if new_layout != layout => signal_entries.push(
+                    DiffEntry::changed(
+                        format!("{name}.{signal_name}"),
+                        format!("{layout} -> {new_layout}"),
+                    )
+                    .breaking(encoding_changed),
+                ),
+                Some(_) => (),
+            }
+        }
+        for signal_name in new_signals.keys() {
+            if !old_signals.contains_key(signal_name) {
+                signal_entries.push(
+                    DiffEntry::added(format!("{name}.{signal_name}")).breaking(encoding_changed),
+                );
+            }
+        }
+    }
+    for name in new_messages.keys() {
+        if !old_messages.contains_key(name) {
+            message_entries.push(DiffEntry::added(*name).breaking(hash_changed));
+        }
+    }
+    (message_entries, signal_entries)
+}
+
+/// names of the messages by bus and id.
+fn message_ids(network: &NetworkRef) -> BTreeMap<(String, String), Vec<&str>> {
+    let mut ids: BTreeMap<(String, String), Vec<&str>> = BTreeMap::new();
+    for msg in network.messages() {
+        ids.entry((msg.bus().name().to_owned(), msg.id().to_string()))
+            .or_default()
+            .push(msg.name());
+    }
+    ids
+}
+
+fn diff_ids(old: &NetworkRef, new: &NetworkRef, hash_changed: bool) -> Vec<DiffEntry> {
+    let mut entries = vec![];
+    let old_ids = message_ids(old);
+    let new_ids = message_ids(new);
+    for (key, names) in &new_ids {
+        let (bus, id) = key;
+        let old_names = old_ids.get(key);
+        // two messages with the same id on a bus can't be decoded,
+        // collisions which already existed in the old config are not part of the diff.
+        let collided = old_names.is_some_and(|old_names| {
+            old_names.len() > 1 && names.iter().all(|name| old_names.contains(name))
+        });
+        if names.len() > 1 && !collided {
+            entries.push(
+                DiffEntry::changed(
+                    format!("{bus} {id}"),
+                    format!("collision between {}", names.join(", ")),
+                )
+                .breaking(true),
+            );
+        }
+        // ids which now belong to a different message.
+        if let Some(old_names) = old_names {
+            if !old_names.iter().any(|name| names.contains(name)) {
+                entries.push(
+                    DiffEntry::changed(
+                        format!("{bus} {id}"),
+                        format!(
+                            "reassigned from {} to {}",
+                            old_names.join(", "),
+                            names.join(", ")
+                        ),
+                    )
+                    .breaking(hash_changed),
+                );
+            }
+        }
+    }
+    entries
+}
+
+fn diff_object_entries(old: &NetworkRef, new: &NetworkRef, hash_changed: bool) -> Vec<DiffEntry> {
+    let mut entries = vec![];
+    for node in old.nodes() {
+        let Some(new_node) = new.nodes().iter().find(|n| n.name() == node.name()) else {
+            continue;
+        };
+        let old_oes: BTreeMap<_, _> = node
+            .object_entries()
+            .iter()
+            .map(|oe| (oe.name(), oe))
+            .collect();
+        let new_oes: BTreeMap<_, _> = new_node
+            .object_entries()
+            .iter()
+            .map(|oe| (oe.name(), oe))
+            .collect();
+        for (name, oe) in &old_oes {
+            let full_name = format!("{}.{name}", node.name());
+            let Some(new_oe) = new_oes.get(name) else {
+                entries.push(DiffEntry::removed(full_name).breaking(hash_changed));
+                continue;
+            };
+            let layout = type_layout(oe.ty());
+            let new_layout = type_layout(new_oe.ty());
+            // ids and types are the encoding of an object entry, units and descriptions are not.
+            let encoding_changed = oe.id() != new_oe.id() || layout != new_layout;
+            if oe.id() != new_oe.id() {
+                entries.push(
+                    DiffEntry::changed(
+                        full_name.clone(),
+                        format!("id {} -> {}", oe.id(), new_oe.id()),
+                    )
+                    .breaking(encoding_changed),
+                );
+            }
+            if oe.unit() != new_oe.unit() {
+                entries.push(DiffEntry::changed(
+                    full_name.clone(),
+                    format!("unit {:?} -> {:?}", oe.unit(), new_oe.unit()),
+                ));
+            }
+            if oe.description() != new_oe.description() {
+                entries.push(DiffEntry::changed(
+                    full_name.clone(),
+                    format!(
+                        "description {:?} -> {:?}",
+                        oe.description(),
+                        new_oe.description()
+                    ),
+                ));
+            }
+            if layout != new_layout {
+                entries.push(
+                    DiffEntry::changed(full_name, format!("type {layout} -> {new_layout}"))
+                        .breaking(encoding_changed),
+                );
+            }
+        }
+        for name in new_oes.keys() {
+            if !old_oes.contains_key(name) {
+                entries.push(
+                    DiffEntry::added(format!("{}.{name}", node.name())).breaking(hash_changed),
+                );
+            }
+        }
+    }
+    entries
+}
+
+fn print_section(title: &str, entries: &[DiffEntry]) {
+    if entries.is_empty() {
+        return;
+    }
+    println!("{title}:");
+    for entry in entries {
+        let breaking = if entry.breaking { " (breaking)" } else { "" };
+        match entry.kind {
+            DiffKind::Added => {
+                cprintln!("<green>  + {}</green><red>{}</red>", entry.name, breaking)
+            }
+            DiffKind::Removed => cprintln!("<red>  - {}{}</red>", entry.name, breaking),
+            DiffKind::Changed => cprintln!(
+                "<yellow>  ~ {} : {}</yellow><red>{}</red>",
+                entry.name,
+                entry.detail,
+                breaking
+            ),
+        }
+    }
+}
+
+/// loads the network config at git revision rev, assuming that config_path
+/// is part of a git repository.
+fn load_network_config_at_rev(config_path: &Path, rev: &str) -> Result<NetworkRef> {
+    let config_path = config_path.canonicalize()?;
+    let config_dir = config_path.parent().unwrap();
+    let repo_dir = PathBuf::from(toplevel(config_dir)?).canonicalize()?;
+    let relative_config_path = config_path.strip_prefix(&repo_dir).unwrap();
+
+    // a worktree per process and revision, such that concurrent diffs don't remove
+    // each others worktree.
+    let rev_dir: String = rev
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut worktree_path = AppData::dir();
+    worktree_path.push(DIFF_WORKTREE_DIR);
+    worktree_path.push(format!("{}-{rev_dir}", std::process::id()));
+    let worktree = worktree_path.to_str().unwrap();
+    if worktree_path.exists() {
+        // leftover of a previous diff, which didn't terminate.
+        let _ = git(&repo_dir, ["worktree", "remove", "--force", worktree]);
+        if worktree_path.exists() {
+            std::fs::remove_dir_all(&worktree_path)?;
+        }
+        git(&repo_dir, ["worktree", "prune"])?;
+    }
+    git(&repo_dir, ["worktree", "add", "--detach", worktree, rev])?;
+    let network = load_network_config(&worktree_path.join(relative_config_path));
+    git(&repo_dir, ["worktree", "remove", "--force", worktree])?;
+    network
+}
+
+pub fn command_config_diff(other: String) -> Result<()> {
+    let appdata = AppData::read()?;
+    let Some(config_path) = config_path(&appdata) else {
+        return Err(Error::NoConfigSelected);
+    };
+    let network = network_config(&appdata)?;
+
+    let other_path = PathBuf::from(&other);
+    let other_network = if other_path.is_file() {
+        load_network_config(&other_path)?
+    } else {
+        load_network_config_at_rev(&config_path, &other)?
+    };
+
+    let old_hash = other_network.portable_hash();
+    let new_hash = network.portable_hash();
+    let hash_changed = old_hash != new_hash;
+
+    let nodes = diff_nodes(&other_network, &network, hash_changed);
+    let (messages, signals) = diff_messages(&other_network, &network, hash_changed);
+    let ids = diff_ids(&other_network, &network, hash_changed);
+    let object_entries = diff_object_entries(&other_network, &network, hash_changed);
+
+    println!("comparing {other} (old) with {config_path:?} (new)");
+    print_section("Nodes", &nodes);
+    print_section("Messages", &messages);
+    print_section("Ids", &ids);
+    print_section("Signals", &signals);
+    print_section("Object Entries", &object_entries);
+
+    if !hash_changed {
+        cprintln!("portable hash : {new_hash} <green>(wire compatible)</green>");
+    } else {
+        cprintln!("portable hash : {old_hash} -> {new_hash} <red>(wire incompatible)</red>");
+    }
+    Ok(())
+}
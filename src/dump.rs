@@ -1,11 +1,27 @@
-use std::{net::SocketAddr, time::Duration};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use canzero_appdata::AppData;
-use canzero_config::config::MessageId;
+use canzero_config::config::{MessageId, NetworkRef};
+use canzero_tcp::tcpcan::TcpCan;
 use canzero_udp::{frame::NetworkDescription, scanner::UdpNetworkScanner};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use color_print::cprintln;
 
-use crate::errors::{Error, Result};
+use crate::{
+    config::{frame_message, message_receivers, message_sender, network_config},
+    decode::signal_raw,
+    errors::{Error, Result},
+    expr::{Expr, FrameContext},
+    pcap::PcapWriter,
+    trace::{bus_names, read_trace, TraceFrame, TraceWriter},
+    trigger::Trigger,
+};
 
 pub async fn discover() -> Result<NetworkDescription> {
     let scanner = UdpNetworkScanner::create().await?;
@@ -16,20 +32,20 @@ pub async fn discover() -> Result<NetworkDescription> {
     }
 }
 
-pub async fn command_dump(filter_msg_names: Vec<String>, filter_ids: Vec<String>) -> Result<()> {
-    if !filter_ids.is_empty() {
-        return Err(Error::NotYetImplemented);
-    }
-    let filter_ids: Vec<MessageId> = vec![];
-    let appdata = AppData::read()?;
-    let network_config = appdata.config()?;
+/// the UTC time of the server timestamp zero.
+/// the timebase of the server is negotiated by the scanner during discovery.
+pub fn server_epoch(network: &NetworkDescription) -> DateTime<Utc> {
+    let since_timebase = Instant::now().duration_since(network.timebase);
+    Utc::now() - chrono::Duration::from_std(since_timebase).unwrap_or_default()
+}
 
+/// discovers a server and connects to it.
+pub async fn connect() -> Result<(NetworkDescription, TcpCan)> {
     let network = discover().await?;
 
     let connection =
         tokio::net::TcpStream::connect(SocketAddr::new(network.server_addr, network.service_port))
-            .await
-            .unwrap();
+            .await?;
 
     cprintln!(
         "<green>Successfully connected to {} at {}:{}</green>",
@@ -38,50 +54,296 @@ pub async fn command_dump(filter_msg_names: Vec<String>, filter_ids: Vec<String>
         network.service_port
     );
 
-    let tcpcan = canzero_tcp::tcpcan::TcpCan::new(connection);
+    Ok((network, TcpCan::new(connection)))
+}
+
+/// parses a time of a trace, e.g. 12.5s, 500ms, 2min or 30 (seconds).
+pub fn parse_time(time: &str) -> std::result::Result<Duration, String> {
+    let time = time.trim();
+    let (value, scale) = if let Some(value) = time.strip_suffix("ms") {
+        (value, 1e-3)
+    } else if let Some(value) = time.strip_suffix("us") {
+        (value, 1e-6)
+    } else if let Some(value) = time.strip_suffix("min") {
+        (value, 60.0)
+    } else if let Some(value) = time.strip_suffix('s') {
+        (value, 1.0)
+    } else {
+        (time, 1.0)
+    };
+    match value.trim().parse::<f64>() {
+        Ok(value) if value >= 0.0 => Ok(Duration::from_secs_f64(value * scale)),
+        _ => Err(format!(
+            "invalid time {time:?}, expected e.g. 12.5s or 500ms"
+        )),
+    }
+}
 
-    loop {
-        let Some(frame) = tcpcan.recv().await else {
-            println!("Connection closed");
-            return Ok(());
-        };
-        let timestamp = &frame.timestamp;
-        let tsec = timestamp.as_secs_f32();
-        let bus_id = &frame.bus_id;
-        let bus = network_config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimeFormat {
+    /// seconds since the timebase of the server.
+    Relative,
+    /// UTC wall-clock time.
+    Absolute,
+    /// seconds since the previous printed frame.
+    Delta,
+}
+
+/// resolves names, filters and prints frames of a live or recorded trace.
+struct Dump {
+    network_config: NetworkRef,
+    filter_msg_names: Vec<String>,
+    filter_ids: Vec<MessageId>,
+    filter_expr: Option<Expr>,
+    changed: bool,
+    verbose: bool,
+    time_format: TimeFormat,
+    /// UTC time of the server timestamp zero, if known.
+    epoch: Option<DateTime<Utc>>,
+    /// timestamp of the last printed frame.
+    last_timestamp: Option<u64>,
+    /// raw signal values of the last printed frame by (bus, id, ide).
+    last_values: HashMap<(u32, u32, bool), Vec<u64>>,
+}
+
+impl Dump {
+    fn print(&mut self, frame: &TraceFrame) {
+        let bus = self
+            .network_config
             .buses()
             .iter()
-            .find(|b| b.id() == *bus_id)
+            .find(|b| b.id() == frame.bus)
             .map_or("can?", |b| b.name());
-        let can_frame = &frame.can_frame;
-        let id = if can_frame.get_ide_flag() {
-            MessageId::ExtendedId(can_frame.get_id())
+        let id = if frame.ide {
+            MessageId::ExtendedId(frame.id)
         } else {
-            MessageId::StandardId(can_frame.get_id())
+            MessageId::StandardId(frame.id)
         };
-        let msg_name = network_config
-            .messages()
-            .iter()
-            .find(|m| m.id() == &id)
-            .map_or("???", |m| m.name());
-        let pass = if !filter_msg_names.is_empty() {
-            filter_msg_names.iter().any(|msg| msg == msg_name)
+        let msg = frame_message(&self.network_config, frame.bus, frame.id, frame.ide);
+        let msg = msg.as_ref();
+        let msg_name = msg.map_or("???", |m| m.name());
+        let pass = if !self.filter_msg_names.is_empty() {
+            self.filter_msg_names.iter().any(|msg| msg == msg_name)
         } else {
             true
         };
         let pass = pass
-            || if !filter_ids.is_empty() {
-                filter_ids.iter().any(|x| x == &id)
+            || if !self.filter_ids.is_empty() {
+                self.filter_ids.iter().any(|x| x == &id)
             } else {
                 false
             };
+        let pass = pass
+            && self.filter_expr.as_ref().map_or(true, |expr| {
+                FrameContext::new(&self.network_config, frame).eval(expr)
+            });
+        if pass && self.changed {
+            // unknown messages compare their whole data.
+            let values = match msg {
+                Some(msg) => msg
+                    .signals()
+                    .iter()
+                    .map(|signal| signal_raw(frame.data, signal))
+                    .collect(),
+                None => vec![frame.data],
+            };
+            let key = (frame.bus, frame.id, frame.ide);
+            if self.last_values.get(&key) == Some(&values) {
+                return;
+            }
+            self.last_values.insert(key, values);
+        }
         if pass {
-            let dlc = can_frame.get_dlc();
-            let mask = 0xFFFFFFFFFFFFFFFFu64
-                .overflowing_shr(64u32 - dlc as u32 * 8u32)
-                .0;
-            let data = can_frame.get_data_u64() & mask;
-            println!("{tsec:08.2}s : {bus:4} {id:5} [{dlc:1}] {data:016X}  ({msg_name})");
+            let time = match self.time_format {
+                TimeFormat::Relative => format!("{:012.6}s", frame.time().as_secs_f64()),
+                TimeFormat::Absolute => match self.epoch {
+                    Some(epoch) => (epoch + chrono::Duration::microseconds(frame.timestamp as i64))
+                        .format("%Y-%m-%dT%H:%M:%S%.6fZ")
+                        .to_string(),
+                    None => format!("{:012.6}s", frame.time().as_secs_f64()),
+                },
+                TimeFormat::Delta => {
+                    let delta = self
+                        .last_timestamp
+                        .map_or(0, |last| frame.timestamp.saturating_sub(last));
+                    format!("+{:.6}s", delta as f64 * 1e-6)
+                }
+            };
+            self.last_timestamp = Some(frame.timestamp);
+            let dlc = frame.dlc;
+            let data = frame.data;
+            let line = format!("{time} : {bus:4} {id:5} [{dlc:1}] {data:016X}  ({msg_name})");
+            match msg.filter(|_| self.verbose) {
+                Some(msg) => {
+                    let sender = message_sender(&self.network_config, msg)
+                        .map_or("???".to_owned(), |node| node.name().to_owned());
+                    let receivers = message_receivers(&self.network_config, msg)
+                        .iter()
+                        .map(|node| node.name().to_owned())
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    // an id, which is only known on another bus, points to wiring or routing errors.
+                    if msg.bus().id() != frame.bus {
+                        cprintln!(
+                            "{line} {sender} -> [{receivers}] <red>expected on {}</red>",
+                            msg.bus().name()
+                        );
+                    } else {
+                        println!("{line} {sender} -> [{receivers}]");
+                    }
+                }
+                None => println!("{line}"),
+            }
         }
     }
 }
+
+pub struct DumpOptions {
+    pub messages: Vec<String>,
+    pub ids: Vec<String>,
+    pub record: Option<PathBuf>,
+    pub pcap: Option<PathBuf>,
+    pub file: Option<PathBuf>,
+    pub from: Option<Duration>,
+    pub to: Option<Duration>,
+    pub filter: Option<Expr>,
+    pub changed: bool,
+    pub verbose: bool,
+    pub time: TimeFormat,
+    pub trigger: Option<Expr>,
+    pub pre: Duration,
+    pub post: Duration,
+    pub trigger_dir: PathBuf,
+}
+
+pub async fn command_dump(options: DumpOptions) -> Result<()> {
+    let DumpOptions {
+        messages: filter_msg_names,
+        ids: filter_ids,
+        record,
+        pcap,
+        file,
+        from,
+        to,
+        filter,
+        changed,
+        verbose,
+        time,
+        trigger,
+        pre,
+        post,
+        trigger_dir,
+    } = options;
+    if !filter_ids.is_empty() {
+        return Err(Error::NotYetImplemented);
+    }
+    let filter_ids: Vec<MessageId> = vec![];
+    let appdata = AppData::read()?;
+    let network_config = network_config(&appdata)?;
+    let buses = bus_names(&network_config);
+
+    let trigger_config = network_config.clone();
+    let new_trigger = move |epoch| {
+        trigger.map(|expr| Trigger::new(trigger_config, expr, pre, post, trigger_dir, epoch))
+    };
+    let mut dump = Dump {
+        network_config,
+        filter_msg_names,
+        filter_ids,
+        filter_expr: filter,
+        changed,
+        verbose,
+        time_format: time,
+        epoch: None,
+        last_timestamp: None,
+        last_values: HashMap::new(),
+    };
+
+    if let Some(file) = file {
+        let trace = read_trace(&file, &buses)?;
+        if time == TimeFormat::Absolute && trace.epoch.is_none() {
+            cprintln!("<yellow>The trace has no clock offset, printing relative times</yellow>");
+        }
+        dump.epoch = trace.epoch;
+        let mut trigger = new_trigger(trace.epoch);
+        for frame in &trace.frames {
+            let time = frame.time();
+            if from.is_some_and(|from| time < from) || to.is_some_and(|to| time > to) {
+                continue;
+            }
+            if let Some(trigger) = &mut trigger {
+                trigger.add(frame)?;
+            }
+            dump.print(frame);
+        }
+        if let Some(trigger) = &mut trigger {
+            trigger.finish()?;
+        }
+        return Ok(());
+    }
+
+    let (network, tcpcan) = connect().await?;
+    let epoch = server_epoch(&network);
+    dump.epoch = Some(epoch);
+    let mut trigger = new_trigger(Some(epoch));
+
+    // all frames are recorded, independent of the filters.
+    let mut recorder = match &record {
+        Some(path) => Some(TraceWriter::create(path, Some(epoch))?),
+        None => None,
+    };
+    let mut pcap_writer = match &pcap {
+        Some(path) => Some(PcapWriter::create(path, &buses, Some(epoch))?),
+        None => None,
+    };
+
+    loop {
+        let frame = tokio::select! {
+            frame = tcpcan.recv() => frame,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let Some(frame) = frame else {
+            println!("Connection closed");
+            break;
+        };
+        let frame = TraceFrame::from_network_frame(&frame);
+        if let Some(recorder) = &mut recorder {
+            recorder.write(&frame)?;
+        }
+        if let Some(pcap_writer) = &mut pcap_writer {
+            pcap_writer.write(&frame)?;
+        }
+        if let Some(trigger) = &mut trigger {
+            trigger.add(&frame)?;
+        }
+        dump.print(&frame);
+    }
+    if let Some(trigger) = &mut trigger {
+        trigger.finish()?;
+    }
+    if let (Some(recorder), Some(path)) = (&mut recorder, &record) {
+        recorder.flush()?;
+        println!("Recorded trace to {path:?}");
+    }
+    if let (Some(pcap_writer), Some(path)) = (&mut pcap_writer, &pcap) {
+        pcap_writer.flush()?;
+        println!("Wrote pcapng capture to {path:?}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time("12.5s"), Ok(Duration::from_millis(12500)));
+        assert_eq!(parse_time("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_time("250us"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_time("2min"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_time(" 30 "), Ok(Duration::from_secs(30)));
+        assert!(parse_time("-1s").is_err());
+        assert!(parse_time("soon").is_err());
+    }
+}
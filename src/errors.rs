@@ -14,9 +14,20 @@ pub enum Error {
     Io(std::io::Error),
     AppDataError(AppDataError),
     MissingDependency(String),
+    MissingArgument(String),
+    UnsupportedArgument(String),
     InvalidResponse,
     InvalidNodeName(String),
     InvalidBusName(String),
+    InvalidConfig(String),
+    InvalidProfileName(String),
+    GitCommandFailed(String),
+    ConfigCheckFailed(usize),
+    GeneratedCodeOutdated(usize),
+    InvalidLogFile(String),
+    InvalidTraceFile(String),
+    UnsupportedFileFormat(String),
+    PlotFailed(String),
     NoServerFound,
     NotYetImplemented,
 }
@@ -43,15 +54,26 @@ impl From<canzero_codegen::errors::Error> for Error {
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            Error::NoConfigSelected => write!(f, "No config was selected with \"config select <path or github repo>\""),
+            Error::NoConfigSelected => write!(f, "No config was selected with \"config set <path or git repo>\""),
             Error::FileNotFound(path) => write!(f, "Failed to find file {path:?}"),
             Error::CodegenError(err) => write!(f, "{err:?}"),
             Error::Io(err) => write!(f, "{err:?}"),
             Error::AppDataError(err) => write!(f, "{err:?}"),
             Error::MissingDependency(dep) => write!(f, "Missing dependency {dep}"),
+            Error::MissingArgument(arg) => write!(f, "Missing argument {arg}"),
+            Error::UnsupportedArgument(arg) => write!(f, "Unsupported argument {arg}"),
             Error::InvalidResponse => write!(f, "Invalid Response"),
             Error::InvalidNodeName(node_name) => write!(f, "Invalid node name : {node_name}"),
             Error::InvalidBusName(bus_name) => write!(f, "Invalid bus name : {bus_name}"),
+            Error::InvalidConfig(err) => write!(f, "Invalid network configuration : {err}"),
+            Error::InvalidProfileName(name) => write!(f, "Invalid profile name or config path : {name}"),
+            Error::GitCommandFailed(cmd) => write!(f, "git command failed : {cmd}"),
+            Error::ConfigCheckFailed(errors) => write!(f, "Config check failed with {errors} errors"),
+            Error::GeneratedCodeOutdated(nodes) => write!(f, "Generated code of {nodes} nodes is outdated"),
+            Error::InvalidLogFile(err) => write!(f, "Invalid log file : {err}"),
+            Error::InvalidTraceFile(err) => write!(f, "Invalid trace file : {err}"),
+            Error::UnsupportedFileFormat(path) => write!(f, "Unsupported file format : {path}"),
+            Error::PlotFailed(err) => write!(f, "Failed to plot : {err}"),
             Error::NoServerFound => write!(f, "No server found"),
             Error::NotYetImplemented => write!(f, "Not yet implemented"),
         }
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+use canzero_appdata::AppData;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+
+use crate::{
+    asc::write_asc,
+    blf::write_blf,
+    config::network_config,
+    errors::{Error, Result},
+    mdf::write_mdf,
+    trace::{bus_names, read_trace},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Mf4,
+    Asc,
+    Blf,
+}
+
+impl ExportFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "mf4" | "mdf" => Some(ExportFormat::Mf4),
+            "asc" => Some(ExportFormat::Asc),
+            "blf" => Some(ExportFormat::Blf),
+            _ => None,
+        }
+    }
+}
+
+/// exports a trace of any format readable by convert.
+pub fn command_export(
+    trace: PathBuf,
+    output: PathBuf,
+    format: Option<ExportFormat>,
+    decode: bool,
+) -> Result<()> {
+    let Some(format) = format.or_else(|| ExportFormat::from_path(&output)) else {
+        return Err(Error::UnsupportedFileFormat(format!("{output:?}")));
+    };
+    let appdata = AppData::read()?;
+    let network_config = network_config(&appdata)?;
+    let trace = read_trace(&trace, &bus_names(&network_config))?;
+    let frames = trace.frames;
+
+    // without a clock offset, the measurement starts at the unix epoch,
+    // such that the times are obviously relative to the timebase of the server.
+    let epoch = match trace.epoch {
+        Some(epoch) => epoch,
+        None => {
+            println!(
+                "The trace has no clock offset, exporting times relative to the server timebase"
+            );
+            DateTime::<Utc>::UNIX_EPOCH
+        }
+    };
+    let bytes = match format {
+        ExportFormat::Mf4 => write_mdf(&network_config, &frames, decode, epoch),
+        ExportFormat::Asc => write_asc(&frames, epoch).into_bytes(),
+        ExportFormat::Blf => write_blf(&frames, epoch),
+    };
+    std::fs::write(&output, bytes)?;
+    println!("Exported {} frames to {output:?}", frames.len());
+    Ok(())
+}
@@ -0,0 +1,501 @@
+use canzero_config::config::{MessageRef, MessageUsage, NetworkRef, NodeRef, SignalRef};
+
+use crate::{
+    config::{frame_message, message_sender},
+    decode::{enum_entry_name, flatten_object_entry, signal_enum, signal_raw, signal_value},
+    trace::TraceFrame,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Literal(Value),
+    /// a variable or, if no such variable exists, the text of the identifier.
+    Identifier(String),
+}
+
+/// a filter expression over the decoded values of a frame, e.g.
+/// bms.voltage < 300 && node == mother_board
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, Op, Operand),
+    Truthy(Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Number(f64),
+    Text(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(expr: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('=', _) => (Token::Op(Op::Eq), 1),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('"' | '\'', _) => {
+                let Some(len) = chars[i + 1..].iter().position(|end| *end == c) else {
+                    return Err(format!("unterminated string at {i}"));
+                };
+                let text: String = chars[i + 1..i + 1 + len].iter().collect();
+                (Token::Text(text), len + 2)
+            }
+            (c, _) if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|c| !(c.is_ascii_alphanumeric() || *c == '.' || *c == '-'))
+                    .unwrap_or(chars.len() - i);
+                let text: String = chars[i..i + len].iter().collect();
+                let number = match text.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok().map(|n| n as f64),
+                    None => text.parse::<f64>().ok(),
+                };
+                match number {
+                    Some(number) => (Token::Number(number), len),
+                    None => return Err(format!("invalid number {text}")),
+                }
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|c| !(c.is_alphanumeric() || *c == '_' || *c == '.'))
+                    .unwrap_or(chars.len() - i);
+                (Token::Identifier(chars[i..i + len].iter().collect()), len)
+            }
+            (c, _) => return Err(format!("unexpected {c:?} at {i}")),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> std::result::Result<Expr, String> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Open) => {
+                self.next();
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing )".to_owned()),
+                }
+            }
+            _ => self.compare(),
+        }
+    }
+
+    fn operand(&mut self) -> std::result::Result<Operand, String> {
+        match self.next() {
+            Some(Token::Identifier(name)) => Ok(Operand::Identifier(name)),
+            Some(Token::Number(number)) => Ok(Operand::Literal(Value::Number(number))),
+            Some(Token::Text(text)) => Ok(Operand::Literal(Value::Text(text))),
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of expression".to_owned()),
+        }
+    }
+
+    fn compare(&mut self) -> std::result::Result<Expr, String> {
+        let lhs = self.operand()?;
+        match self.peek() {
+            Some(Token::Op(op)) => {
+                let op = *op;
+                self.next();
+                Ok(Expr::Compare(lhs, op, self.operand()?))
+            }
+            _ => Ok(Expr::Truthy(lhs)),
+        }
+    }
+}
+
+/// parses an expression, used as clap value parser.
+pub fn parse_expr(expr: &str) -> std::result::Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+    };
+    let parsed = parser.or()?;
+    match parser.peek() {
+        None => Ok(parsed),
+        Some(token) => Err(format!("unexpected {token:?}")),
+    }
+}
+
+/// the variables of a frame:
+/// id, bus, node (sender), message,
+/// the signals of the message as <signal> and <message>.<signal>
+/// and the object entries of stream messages as <node>.<entry>[.<field>].
+pub struct FrameContext<'a> {
+    network: &'a NetworkRef,
+    frame: &'a TraceFrame,
+    msg: Option<MessageRef>,
+    sender: Option<NodeRef>,
+}
+
+impl<'a> FrameContext<'a> {
+    pub fn new(network: &'a NetworkRef, frame: &'a TraceFrame) -> Self {
+        let msg = frame_message(network, frame.bus, frame.id, frame.ide);
+        let sender = msg.as_ref().and_then(|msg| message_sender(network, msg));
+        FrameContext {
+            network,
+            frame,
+            msg,
+            sender,
+        }
+    }
+
+    fn signal(&self, msg: &MessageRef, signal: &SignalRef) -> Value {
+        let data = self.frame.data;
+        match signal_enum(msg, signal)
+            .and_then(|ty| enum_entry_name(&ty, signal_raw(data, signal)).map(str::to_owned))
+        {
+            Some(name) => Value::Text(name),
+            None => Value::Number(signal_value(data, signal)),
+        }
+    }
+
+    fn object_entry(&self, msg: &MessageRef, node: &str, path: &str) -> Option<Value> {
+        let (MessageUsage::Stream(stream), Some(encoding)) = (msg.usage(), msg.encoding()) else {
+            return None;
+        };
+        for (oe, oe_encoding) in stream.mapping().iter().zip(encoding.attributes()) {
+            let Some(oe) = oe else {
+                continue;
+            };
+            for (field, signal) in flatten_object_entry(oe_encoding) {
+                let name = if field == "value" {
+                    format!("{node}.{}", oe.name())
+                } else {
+                    format!("{node}.{}.{field}", oe.name())
+                };
+                if name == path {
+                    return Some(self.signal(msg, &signal));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn variable(&self, name: &str) -> Option<Value> {
+        match name {
+            "id" => return Some(Value::Number(self.frame.id as f64)),
+            "bus" => {
+                return self
+                    .network
+                    .buses()
+                    .iter()
+                    .find(|bus| bus.id() == self.frame.bus)
+                    .map(|bus| Value::Text(bus.name().to_owned()))
+            }
+            "node" => {
+                return self
+                    .sender
+                    .as_ref()
+                    .map(|n| Value::Text(n.name().to_owned()))
+            }
+            "message" | "msg" => {
+                return self
+                    .msg
+                    .as_ref()
+                    .map(|msg| Value::Text(msg.name().to_owned()))
+            }
+            _ => (),
+        }
+        let msg = self.msg.as_ref()?;
+        let signal_name = name
+            .strip_prefix(msg.name())
+            .and_then(|name| name.strip_prefix('.'))
+            .unwrap_or(name);
+        if let Some(signal) = msg.signals().iter().find(|s| s.name() == signal_name) {
+            return Some(self.signal(msg, signal));
+        }
+        let sender = self.sender.as_ref()?;
+        self.object_entry(msg, sender.name(), name)
+    }
+
+    pub fn eval(&self, expr: &Expr) -> bool {
+        eval(expr, &|name| self.variable(name))
+    }
+
+    /// the value of an expression, none if the frame has none of its variables.
+    pub fn condition(&self, expr: &Expr) -> Option<bool> {
+        let variable = |name: &str| self.variable(name);
+        defines(expr, &variable).then(|| eval(expr, &variable))
+    }
+}
+
+/// whether one of the variables of an expression has a value.
+fn defines(expr: &Expr, variable: &dyn Fn(&str) -> Option<Value>) -> bool {
+    let operand = |operand: &Operand| match operand {
+        Operand::Identifier(name) => variable(name).is_some(),
+        Operand::Literal(_) => false,
+    };
+    match expr {
+        Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+            defines(lhs, variable) || defines(rhs, variable)
+        }
+        Expr::Not(expr) => defines(expr, variable),
+        Expr::Compare(lhs, _, rhs) => operand(lhs) || operand(rhs),
+        Expr::Truthy(value) => operand(value),
+    }
+}
+
+/// a comparison needs its identifiers to be variables,
+/// except if both are identifiers, one of them may be a plain text (e.g. node == mother_board).
+fn operands(
+    lhs: &Operand,
+    rhs: &Operand,
+    variable: &dyn Fn(&str) -> Option<Value>,
+) -> Option<(Value, Value)> {
+    match (lhs, rhs) {
+        (Operand::Identifier(a), Operand::Identifier(b)) => match (variable(a), variable(b)) {
+            (None, None) => None,
+            (a_value, b_value) => Some((
+                a_value.unwrap_or(Value::Text(a.clone())),
+                b_value.unwrap_or(Value::Text(b.clone())),
+            )),
+        },
+        (Operand::Identifier(a), Operand::Literal(b)) => Some((variable(a)?, b.clone())),
+        (Operand::Literal(a), Operand::Identifier(b)) => Some((a.clone(), variable(b)?)),
+        (Operand::Literal(a), Operand::Literal(b)) => Some((a.clone(), b.clone())),
+    }
+}
+
+/// evaluates an expression over the values of variable.
+fn eval(expr: &Expr, variable: &dyn Fn(&str) -> Option<Value>) -> bool {
+    match expr {
+        Expr::And(lhs, rhs) => eval(lhs, variable) && eval(rhs, variable),
+        Expr::Or(lhs, rhs) => eval(lhs, variable) || eval(rhs, variable),
+        Expr::Not(expr) => !eval(expr, variable),
+        Expr::Truthy(operand) => match operand {
+            Operand::Identifier(name) => match variable(name) {
+                Some(Value::Number(number)) => number != 0.0,
+                Some(Value::Text(_)) => true,
+                None => false,
+            },
+            Operand::Literal(Value::Number(number)) => *number != 0.0,
+            Operand::Literal(Value::Text(text)) => !text.is_empty(),
+        },
+        Expr::Compare(lhs, op, rhs) => match operands(lhs, rhs, variable) {
+            Some((Value::Number(a), Value::Number(b))) => compare(a.partial_cmp(&b), *op),
+            Some((Value::Text(a), Value::Text(b))) => compare(Some(a.cmp(&b)), *op),
+            _ => false,
+        },
+    }
+}
+
+fn compare(ordering: Option<std::cmp::Ordering>, op: Op) -> bool {
+    use std::cmp::Ordering::*;
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match op {
+        Op::Eq => ordering == Equal,
+        Op::Ne => ordering != Equal,
+        Op::Lt => ordering == Less,
+        Op::Le => ordering != Greater,
+        Op::Gt => ordering == Greater,
+        Op::Ge => ordering != Less,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(name: &str) -> Operand {
+        Operand::Identifier(name.to_owned())
+    }
+
+    fn number(number: f64) -> Operand {
+        Operand::Literal(Value::Number(number))
+    }
+
+    fn text(text: &str) -> Operand {
+        Operand::Literal(Value::Text(text.to_owned()))
+    }
+
+    fn truthy(name: &str) -> Box<Expr> {
+        Box::new(Expr::Truthy(id(name)))
+    }
+
+    #[test]
+    fn and_binds_stronger_than_or() {
+        assert_eq!(
+            parse_expr("a || b && c"),
+            Ok(Expr::Or(
+                truthy("a"),
+                Box::new(Expr::And(truthy("b"), truthy("c")))
+            ))
+        );
+        assert_eq!(
+            parse_expr("(a || b) && !c"),
+            Ok(Expr::And(
+                Box::new(Expr::Or(truthy("a"), truthy("b"))),
+                Box::new(Expr::Not(truthy("c")))
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert_eq!(
+            parse_expr("id=0x123"),
+            Ok(Expr::Compare(id("id"), Op::Eq, number(291.0)))
+        );
+        assert_eq!(
+            parse_expr("bms.voltage >= -1.5"),
+            Ok(Expr::Compare(id("bms.voltage"), Op::Ge, number(-1.5)))
+        );
+        assert!(parse_expr("id == 0xZZ").is_err());
+    }
+
+    #[test]
+    fn parses_quoted_text() {
+        assert_eq!(
+            parse_expr(r#"node == "mother board""#),
+            Ok(Expr::Compare(id("node"), Op::Eq, text("mother board")))
+        );
+        assert_eq!(
+            parse_expr("state != 'a \"b\"'"),
+            Ok(Expr::Compare(id("state"), Op::Ne, text("a \"b\"")))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(parse_expr("node == 'mother").is_err());
+        assert!(parse_expr("(a || b").is_err());
+        assert!(parse_expr("a b").is_err());
+        assert!(parse_expr("a &&").is_err());
+        assert!(parse_expr("a # b").is_err());
+    }
+
+    fn eval_str(expr: &str) -> bool {
+        let variable = |name: &str| match name {
+            "id" => Some(Value::Number(291.0)),
+            "voltage" => Some(Value::Number(250.0)),
+            "node" => Some(Value::Text("mother_board".to_owned())),
+            "state" => Some(Value::Text("RUNNING".to_owned())),
+            _ => None,
+        };
+        eval(&parse_expr(expr).unwrap(), &variable)
+    }
+
+    #[test]
+    fn evaluates_comparisons() {
+        assert!(eval_str("id == 0x123"));
+        assert!(eval_str("voltage < 300 && voltage >= 250"));
+        assert!(!eval_str("voltage > 250"));
+        assert!(eval_str("state == 'RUNNING'"));
+        assert!(eval_str("node == mother_board"));
+        assert!(eval_str("mother_board == node"));
+        assert!(!eval_str("node != mother_board"));
+        // a number never equals a text.
+        assert!(!eval_str("state == 1"));
+    }
+
+    #[test]
+    fn conditions_need_a_variable() {
+        let variable = |name: &str| match name {
+            "id" => Some(Value::Number(291.0)),
+            _ => None,
+        };
+        let defines_str = |expr: &str| defines(&parse_expr(expr).unwrap(), &variable);
+        assert!(defines_str("id == 0x123"));
+        assert!(defines_str("current > 10 || !(id < 3)"));
+        assert!(!defines_str("current > 10"));
+        assert!(!defines_str("foo == bar"));
+        assert!(!defines_str("1 == 1"));
+    }
+
+    #[test]
+    fn unknown_variables_are_false() {
+        assert!(!eval_str("current > 10"));
+        assert!(!eval_str("current"));
+        assert!(eval_str("!current"));
+        assert!(!eval_str("foo == bar"));
+        assert!(eval_str("id && node"));
+    }
+}
@@ -1,8 +1,30 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
+use build_time::build_time_local;
 use canzero_appdata::AppData;
+use canzero_config::config::NetworkRef;
+use clap::ValueEnum;
+use color_print::cprintln;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 
-use crate::errors::{Error, Result};
+use crate::{
+    config::{config_files, config_path, config_source, network_config},
+    errors::{Error, Result},
+    generate_python::generate_python,
+    generate_rust::generate_rust,
+    git::git,
+};
+
+const MANIFEST_FILE: &'static str = "canzero-manifest.yaml";
+const CHECK_DIR: &'static str = "generate-check";
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// interval of --watch, while no config is selected.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
 
 fn rec_create_dir(dir : &Path) -> Result<()>{
    if !dir.is_dir() {
@@ -14,27 +36,524 @@ fn rec_create_dir(dir : &Path) -> Result<()>{
     Ok(())
 }
 
-pub fn command_generate(node_name : &str, output_dir : &PathBuf) -> Result<()> {
-    let appdata = AppData::read()?;
-    let network_config = appdata.config()?;
-    let output_dir = output_dir.clone();
-    if !output_dir.exists() {
-        return Err(Error::FileNotFound(output_dir.to_str().unwrap().to_owned()));
+/// written next to the generated files, records from what they were generated.
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    node: String,
+    config_path: Option<PathBuf>,
+    config_revision: Option<String>,
+    portable_hash: u64,
+    cli_build_time: String,
+    files: Vec<PathBuf>,
+}
+
+/// the git commit of the config, suffixed with -dirty if there are uncommitted changes.
+fn config_revision(config_path: &Path) -> Option<String> {
+    if let Ok(Some(source)) = config_source() {
+        if source.config_path() == config_path {
+            return Some(source.commit);
+        }
     }
-    
-    rec_create_dir(&output_dir)?;
+    let config_dir = config_path.parent()?;
+    let commit = git(config_dir, ["rev-parse", "HEAD"]).ok()?;
+    let status = git(config_dir, ["status", "--porcelain", "."]).ok()?;
+    if status.is_empty() {
+        Some(commit)
+    } else {
+        Some(format!("{commit}-dirty"))
+    }
+}
 
-    let mut options = canzero_codegen::options::Options::default();
+/// codegen options of a node, given on the command line or in a mapping file.
+/// All options except output_dir have a flag of the same name, which applies to every
+/// generated node and takes precedence over the mapping file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeOptions {
+    /// only used in mapping files, on the command line the output directory is
+    /// the positional argument or a subdirectory of --all.
+    pub output_dir: Option<PathBuf>,
+    /// relative to the output directory, may contain subdirectories e.g. src/canzero.c.
+    pub source_file: Option<String>,
+    /// relative to the output directory, may contain subdirectories e.g. include/canzero.h.
+    pub header_file: Option<String>,
+    pub namespace: Option<String>,
+    pub header_guard: Option<String>,
+}
 
-    let mut source_file_path = output_dir.clone();
-    source_file_path.push("canzero.cpp");
-    options.set_source_file_path(source_file_path.to_str().unwrap());
+impl NodeOptions {
+    /// options which are set in overrides take precedence.
+    fn merge(&self, overrides: &NodeOptions) -> NodeOptions {
+        NodeOptions {
+            output_dir: overrides.output_dir.clone().or(self.output_dir.clone()),
+            source_file: overrides.source_file.clone().or(self.source_file.clone()),
+            header_file: overrides.header_file.clone().or(self.header_file.clone()),
+            namespace: overrides.namespace.clone().or(self.namespace.clone()),
+            header_guard: overrides.header_guard.clone().or(self.header_guard.clone()),
+        }
+    }
 
-    let mut header_file_path = output_dir.clone();
-    header_file_path.push("canzero.h");
-    options.set_header_file_path(header_file_path.to_str().unwrap());
+    fn source_file(&self) -> &str {
+        self.source_file.as_deref().unwrap_or("canzero.cpp")
+    }
 
-    canzero_codegen::generate(node_name, network_config, options)?;
+    fn header_file(&self) -> &str {
+        self.header_file.as_deref().unwrap_or("canzero.h")
+    }
+}
 
-    Ok(())
+/// entry of a mapping file, either just the output directory or all options of the node.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MappingEntry {
+    OutputDir(PathBuf),
+    Options(NodeOptions),
+}
+
+fn print_diff(file: &Path, previous: &str, content: &str) {
+    let diff = TextDiff::from_lines(previous, content);
+    let file = file.to_str().unwrap();
+    for line in diff
+        .unified_diff()
+        .header(&format!("a/{file}"), &format!("b/{file}"))
+        .to_string()
+        .lines()
+    {
+        if line.starts_with("+++") || line.starts_with("---") {
+            cprintln!("<bold>{line}</bold>");
+        } else if line.starts_with('+') {
+            cprintln!("<green>{line}</green>");
+        } else if line.starts_with('-') {
+            cprintln!("<red>{line}</red>");
+        } else if line.starts_with("@@") {
+            cprintln!("<cyan>{line}</cyan>");
+        } else {
+            println!("{line}");
+        }
+    }
+}
+
+struct GeneratedNode {
+    node_name: String,
+    files: Vec<PathBuf>,
+    /// for --check and --dry-run, whether the files on disk differ from the generated ones.
+    changed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerateMode {
+    Write,
+    /// fail if the files on disk differ from the generated ones.
+    Check,
+    /// print a diff of the files on disk and the generated ones.
+    DryRun,
+}
+
+struct Generator {
+    network_config: NetworkRef,
+    config_path: Option<PathBuf>,
+    /// options given on the command line, which apply to all nodes.
+    options: NodeOptions,
+    mode: GenerateMode,
+    create_dirs: bool,
+    /// only writes nodes, whose generated code changed, used by --watch
+    /// such that unaffected nodes are not rebuilt.
+    only_changed: bool,
+}
+
+impl Generator {
+    fn new(
+        options: NodeOptions,
+        mode: GenerateMode,
+        create_dirs: bool,
+        only_changed: bool,
+    ) -> Result<Self> {
+        let appdata = AppData::read()?;
+        Ok(Generator {
+            network_config: network_config(&appdata)?,
+            config_path: config_path(&appdata),
+            options,
+            mode,
+            create_dirs,
+            only_changed,
+        })
+    }
+
+    /// fails for missing output directories, unless they should be created.
+    fn ensure_dir(&self, dir: &Path) -> Result<()> {
+        if dir.exists() || self.mode != GenerateMode::Write {
+            return Ok(());
+        }
+        if !self.create_dirs {
+            return Err(Error::FileNotFound(format!(
+                "{} (use --create-dirs to create it)",
+                dir.to_str().unwrap()
+            )));
+        }
+        rec_create_dir(dir)
+    }
+
+    fn render(
+        &self,
+        node_name: &str,
+        output_dir: &Path,
+        node_options: &NodeOptions,
+    ) -> Result<Vec<PathBuf>> {
+        if !self
+            .network_config
+            .nodes()
+            .iter()
+            .any(|n| n.name() == node_name)
+        {
+            return Err(Error::InvalidNodeName(node_name.to_owned()));
+        }
+        rec_create_dir(output_dir)?;
+
+        let mut options = canzero_codegen::options::Options::default();
+
+        let source_file_path = output_dir.join(node_options.source_file());
+        let header_file_path = output_dir.join(node_options.header_file());
+        for file_path in [&source_file_path, &header_file_path] {
+            if let Some(parent) = file_path.parent() {
+                rec_create_dir(parent)?;
+            }
+        }
+        options.set_source_file_path(source_file_path.to_str().unwrap());
+        options.set_header_file_path(header_file_path.to_str().unwrap());
+        if let Some(namespace) = &node_options.namespace {
+            options.set_namespace(namespace);
+        }
+        if let Some(header_guard) = &node_options.header_guard {
+            options.set_header_guard(header_guard);
+        }
+
+        canzero_codegen::generate(node_name, self.network_config.clone(), options)?;
+        Ok(vec![header_file_path, source_file_path])
+    }
+
+    fn write_manifest(&self, node_name: &str, output_dir: &Path, files: &[PathBuf]) -> Result<()> {
+        let manifest = Manifest {
+            node: node_name.to_owned(),
+            config_path: self.config_path.clone(),
+            config_revision: self.config_path.as_deref().and_then(config_revision),
+            portable_hash: self.network_config.portable_hash(),
+            cli_build_time: build_time_local!().to_owned(),
+            files: files
+                .iter()
+                .map(|f| f.strip_prefix(output_dir).unwrap().to_path_buf())
+                .collect(),
+        };
+        let content = serde_yaml::to_string(&manifest)
+            .map_err(|err| Error::InvalidConfig(format!("{err:?}")))?;
+        std::fs::write(output_dir.join(MANIFEST_FILE), content)?;
+        Ok(())
+    }
+
+    fn generate_node(
+        &self,
+        node_name: &str,
+        output_dir: &Path,
+        node_options: &NodeOptions,
+    ) -> Result<GeneratedNode> {
+        let node_options = node_options.merge(&self.options);
+        if self.mode != GenerateMode::Write {
+            return self.compare_node(node_name, output_dir, &node_options);
+        }
+        if self.only_changed {
+            let compared = self.compare_node(node_name, output_dir, &node_options)?;
+            if !compared.changed {
+                return Ok(compared);
+            }
+        }
+        let previous: Vec<Option<Vec<u8>>> =
+            [node_options.header_file(), node_options.source_file()]
+                .iter()
+                .map(|f| std::fs::read(output_dir.join(f)).ok())
+                .collect();
+
+        let files = self.render(node_name, output_dir, &node_options)?;
+        self.write_manifest(node_name, output_dir, &files)?;
+
+        let changed = files
+            .iter()
+            .zip(previous)
+            .any(|(file, previous)| std::fs::read(file).ok() != previous);
+
+        Ok(GeneratedNode {
+            node_name: node_name.to_owned(),
+            files,
+            changed,
+        })
+    }
+
+    /// renders the node into a scratch directory and compares it with output_dir.
+    fn compare_node(
+        &self,
+        node_name: &str,
+        output_dir: &Path,
+        node_options: &NodeOptions,
+    ) -> Result<GeneratedNode> {
+        let mut check_dir = AppData::dir();
+        check_dir.push(CHECK_DIR);
+        check_dir.push(node_name);
+        if check_dir.exists() {
+            std::fs::remove_dir_all(&check_dir)?;
+        }
+        let rendered = self.render(node_name, &check_dir, node_options)?;
+        let mut changed = false;
+        let mut files = vec![];
+        for rendered_file in &rendered {
+            let file = output_dir.join(rendered_file.strip_prefix(&check_dir).unwrap());
+            let previous = std::fs::read_to_string(&file).unwrap_or_default();
+            let content = std::fs::read_to_string(rendered_file)?;
+            if previous != content || !file.exists() {
+                changed = true;
+                if self.mode == GenerateMode::DryRun {
+                    print_diff(&file, &previous, &content);
+                }
+            }
+            files.push(file);
+        }
+        std::fs::remove_dir_all(&check_dir)?;
+        Ok(GeneratedNode {
+            node_name: node_name.to_owned(),
+            files,
+            changed,
+        })
+    }
+
+    /// writes, checks or diffs a file, which is rendered by the cli itself.
+    fn emit_file(&self, name: &str, path: &Path, content: &str) -> Result<GeneratedNode> {
+        let previous = std::fs::read_to_string(path).ok();
+        let changed = previous.as_deref() != Some(content);
+        match self.mode {
+            GenerateMode::Write => {
+                if changed || !self.only_changed {
+                    std::fs::write(path, content)?
+                }
+            }
+            GenerateMode::Check => (),
+            GenerateMode::DryRun => {
+                if changed {
+                    print_diff(path, previous.as_deref().unwrap_or_default(), content);
+                }
+            }
+        }
+        Ok(GeneratedNode {
+            node_name: name.to_owned(),
+            files: vec![path.to_path_buf()],
+            changed,
+        })
+    }
+
+    fn finish(&self, generated: &[GeneratedNode]) -> Result<()> {
+        println!(
+            "generated from portable hash {}",
+            self.network_config.portable_hash()
+        );
+        for node in generated {
+            let files = node
+                .files
+                .iter()
+                .map(|f| f.to_str().unwrap())
+                .collect::<Vec<_>>()
+                .join(", ");
+            match (self.mode, node.changed) {
+                (GenerateMode::Write, true) | (GenerateMode::DryRun, true) => {
+                    cprintln!("{:25} : <yellow>{:9}</yellow> {files}", node.node_name, "CHANGED")
+                }
+                (GenerateMode::Write, false) | (GenerateMode::DryRun, false) => {
+                    cprintln!("{:25} : <green>{:9}</green> {files}", node.node_name, "UNCHANGED")
+                }
+                (GenerateMode::Check, true) => {
+                    cprintln!("{:25} : <red>{:9}</red> {files}", node.node_name, "OUTDATED")
+                }
+                (GenerateMode::Check, false) => {
+                    cprintln!("{:25} : <green>{:9}</green> {files}", node.node_name, "UPTODATE")
+                }
+            }
+        }
+        if self.mode == GenerateMode::DryRun {
+            println!("dry run, no files were written");
+        }
+        let outdated = generated.iter().filter(|n| n.changed).count();
+        if self.mode == GenerateMode::Check && outdated != 0 {
+            return Err(Error::GeneratedCodeOutdated(outdated));
+        }
+        Ok(())
+    }
+}
+
+/// the language of the generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    C,
+    Python,
+    Rust,
+}
+
+impl Language {
+    fn file_name(&self) -> &'static str {
+        match self {
+            Language::C => "canzero.cpp",
+            Language::Python => "canzero.py",
+            Language::Rust => "canzero.rs",
+        }
+    }
+}
+
+/// the nodes, which are generated by a single generate command.
+pub enum GenerateTarget {
+    Node {
+        node_name: String,
+        output_dir: PathBuf,
+    },
+    /// every node of the network into its own subdirectory.
+    All(PathBuf),
+    /// the nodes listed in a yaml file, which maps node names to output directories
+    /// relative to the mapping file or to the options of the node.
+    Mapping(PathBuf),
+    /// a single module for the whole network in a language other than c.
+    Network { language: Language, output: PathBuf },
+}
+
+fn generate_node(generator: &Generator, node_name: &str, output_dir: &PathBuf) -> Result<()> {
+    generator.ensure_dir(output_dir)?;
+
+    let generated = generator.generate_node(node_name, output_dir, &NodeOptions::default())?;
+    generator.finish(&[generated])
+}
+
+fn generate_all(generator: &Generator, output_root: &PathBuf) -> Result<()> {
+    generator.ensure_dir(output_root)?;
+
+    let mut generated = vec![];
+    for node in generator.network_config.nodes() {
+        generated.push(generator.generate_node(
+            node.name(),
+            &output_root.join(node.name()),
+            &NodeOptions::default(),
+        )?);
+    }
+    generator.finish(&generated)
+}
+
+fn generate_mapping(generator: &Generator, mapping_path: &PathBuf) -> Result<()> {
+    if !mapping_path.exists() {
+        return Err(Error::FileNotFound(mapping_path.to_str().unwrap().to_owned()));
+    }
+    let content = std::fs::read_to_string(mapping_path)?;
+    let mapping: BTreeMap<String, MappingEntry> = serde_yaml::from_str(&content)
+        .map_err(|err| Error::InvalidConfig(format!("{err:?}")))?;
+    let mapping_dir = mapping_path.parent().unwrap_or(Path::new("."));
+
+    let mut generated = vec![];
+    for (node_name, entry) in mapping {
+        let node_options = match entry {
+            MappingEntry::OutputDir(output_dir) => NodeOptions {
+                output_dir: Some(output_dir),
+                ..Default::default()
+            },
+            MappingEntry::Options(node_options) => node_options,
+        };
+        let Some(output_dir) = &node_options.output_dir else {
+            return Err(Error::InvalidConfig(format!(
+                "missing output_dir of node {node_name} in {mapping_path:?}"
+            )));
+        };
+        let output_dir = mapping_dir.join(output_dir);
+        generator.ensure_dir(&output_dir)?;
+        generated.push(generator.generate_node(&node_name, &output_dir, &node_options)?);
+    }
+    generator.finish(&generated)
+}
+
+fn generate_network(generator: &Generator, language: Language, output: &PathBuf) -> Result<()> {
+    let content = match language {
+        Language::Python => generate_python(&generator.network_config),
+        Language::Rust => generate_rust(&generator.network_config)?,
+        Language::C => unreachable!("c code is generated per node"),
+    };
+    let path = if output.is_dir() {
+        output.join(language.file_name())
+    } else {
+        output.clone()
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        generator.ensure_dir(parent)?;
+    }
+    let name = format!("{language:?}").to_lowercase();
+    let generated = generator.emit_file(&name, &path, &content)?;
+    generator.finish(&[generated])
+}
+
+fn generate(
+    target: &GenerateTarget,
+    options: &NodeOptions,
+    mode: GenerateMode,
+    create_dirs: bool,
+    only_changed: bool,
+) -> Result<()> {
+    let generator = Generator::new(options.clone(), mode, create_dirs, only_changed)?;
+    match target {
+        GenerateTarget::Node {
+            node_name,
+            output_dir,
+        } => generate_node(&generator, node_name, output_dir),
+        GenerateTarget::All(output_root) => generate_all(&generator, output_root),
+        GenerateTarget::Mapping(mapping_path) => generate_mapping(&generator, mapping_path),
+        GenerateTarget::Network { language, output } => {
+            generate_network(&generator, *language, output)
+        }
+    }
+}
+
+fn mtimes(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+pub fn command_generate(
+    target: GenerateTarget,
+    options: NodeOptions,
+    mode: GenerateMode,
+    create_dirs: bool,
+    watch: bool,
+) -> Result<()> {
+    if !watch {
+        return generate(&target, &options, mode, create_dirs, false);
+    }
+    let mut files = vec![];
+    loop {
+        if let Err(err) = generate(&target, &options, mode, create_dirs, true) {
+            cprintln!("<red>{err:?}</red>");
+        }
+        // an invalid config might not list all its files, keep watching the previous ones.
+        match AppData::read().map_err(Error::from).and_then(|appdata| config_files(&appdata)) {
+            Ok(config_files) => files = config_files,
+            Err(err) => cprintln!("<red>{err:?}</red>"),
+        }
+        if files.is_empty() {
+            // without any listed files, at least the main config file is watched.
+            match AppData::read().map(|appdata| config_path(&appdata)) {
+                Ok(Some(path)) => files = vec![path],
+                Ok(None) => cprintln!("<red>{:?}</red>", Error::NoConfigSelected),
+                Err(err) => cprintln!("<red>{:?}</red>", Error::from(err)),
+            }
+        }
+        if files.is_empty() {
+            println!("Retrying in {}s", RETRY_INTERVAL.as_secs());
+            std::thread::sleep(RETRY_INTERVAL);
+            continue;
+        }
+        let previous = mtimes(&files);
+        println!("Watching {} config files for changes", files.len());
+        while mtimes(&files) == previous {
+            std::thread::sleep(WATCH_INTERVAL);
+        }
+        // editors often write files in multiple steps.
+        std::thread::sleep(WATCH_INTERVAL);
+    }
 }
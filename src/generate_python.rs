@@ -0,0 +1,160 @@
+use std::fmt::Write;
+
+use canzero_config::config::{NetworkRef, SignalType, Type};
+
+use crate::decode::signal_enum;
+
+const PRELUDE: &'static str = r#"from dataclasses import dataclass
+from enum import IntEnum
+from typing import Dict, Optional, Tuple, Type, Union
+
+
+@dataclass(frozen=True)
+class Signal:
+    name: str
+    bit_offset: int
+    size: int
+    signed: bool
+    scale: Optional[float]
+    offset: Optional[float]
+    enum: Optional[Type[IntEnum]] = None
+
+    @property
+    def mask(self) -> int:
+        return (1 << self.size) - 1
+
+    def decode(self, data: int) -> Union[int, float, IntEnum]:
+        raw = (data >> self.bit_offset) & self.mask
+        if self.signed and raw & (1 << (self.size - 1)):
+            raw -= 1 << self.size
+        if self.scale is not None:
+            return raw * self.scale + self.offset
+        if self.enum is not None:
+            try:
+                return self.enum(raw)
+            except ValueError:
+                return raw
+        return raw
+
+    def encode(self, value: Union[int, float, IntEnum]) -> int:
+        if self.scale is not None:
+            raw = round((value - self.offset) / self.scale)
+        else:
+            raw = int(value)
+        return (raw & self.mask) << self.bit_offset
+
+
+@dataclass(frozen=True)
+class Message:
+    name: str
+    id: int
+    extended: bool
+    bus: str
+    dlc: int
+    signals: Tuple[Signal, ...]
+
+    def decode(self, data: Union[bytes, int]) -> Dict[str, Union[int, float, IntEnum]]:
+        if isinstance(data, (bytes, bytearray)):
+            data = int.from_bytes(data, "little")
+        return {signal.name: signal.decode(data) for signal in self.signals}
+
+    def encode(self, **values: Union[int, float, IntEnum]) -> bytes:
+        data = 0
+        for signal in self.signals:
+            data |= signal.encode(values[signal.name])
+        return data.to_bytes(self.dlc, "little")
+"#;
+
+const POSTLUDE: &'static str = r#"
+MESSAGES_BY_ID: Dict[Tuple[str, int, bool], Message] = {
+    (msg.bus, msg.id, msg.extended): msg for msg in MESSAGES.values()
+}
+
+
+def find_message(bus: str, can_id: int, extended: bool = False) -> Optional[Message]:
+    return MESSAGES_BY_ID.get((bus, can_id, extended))
+
+
+def decode(
+    bus: str, can_id: int, data: Union[bytes, int], extended: bool = False
+) -> Optional[Tuple[str, Dict[str, Union[int, float, IntEnum]]]]:
+    msg = find_message(bus, can_id, extended)
+    if msg is None:
+        return None
+    return msg.name, msg.decode(data)
+
+
+def encode(name: str, **values: Union[int, float, IntEnum]) -> Tuple[Message, bytes]:
+    msg = MESSAGES[name]
+    return msg, msg.encode(**values)
+"#;
+
+fn python_float(value: f64) -> String {
+    format!("{value:?}")
+}
+
+/// renders a python module with the messages, signals and enums of the network.
+pub fn generate_python(network: &NetworkRef) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# generated by canzero-cli, do not edit!\n# portable hash of the network config : {}\n",
+        network.portable_hash()
+    )
+    .unwrap();
+    out.push_str(PRELUDE);
+    writeln!(out, "\n\nPORTABLE_HASH = {}\n", network.portable_hash()).unwrap();
+
+    for ty in network.types() {
+        let Type::Enum { name, entries, .. } = &**ty else {
+            continue;
+        };
+        writeln!(out, "\nclass {name}(IntEnum):").unwrap();
+        if entries.is_empty() {
+            writeln!(out, "    pass").unwrap();
+        }
+        for (entry_name, value) in entries {
+            writeln!(out, "    {entry_name} = {value}").unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    writeln!(out, "\nMESSAGES: Dict[str, Message] = {{").unwrap();
+    for msg in network.messages() {
+        writeln!(out, "    \"{}\": Message(", msg.name()).unwrap();
+        writeln!(out, "        name=\"{}\",", msg.name()).unwrap();
+        writeln!(out, "        id=0x{:X},", msg.id().as_u32()).unwrap();
+        let extended = if msg.id().ide() { "True" } else { "False" };
+        writeln!(out, "        extended={extended},").unwrap();
+        writeln!(out, "        bus=\"{}\",", msg.bus().name()).unwrap();
+        writeln!(out, "        dlc={},", msg.dlc()).unwrap();
+        writeln!(out, "        signals=(").unwrap();
+        for signal in msg.signals() {
+            let (signed, scale, offset) = match signal.ty() {
+                SignalType::UnsignedInt { .. } => ("False", "None".to_owned(), "None".to_owned()),
+                SignalType::SignedInt { .. } => ("True", "None".to_owned(), "None".to_owned()),
+                SignalType::Decimal { offset, scale, .. } => {
+                    ("False", python_float(*scale), python_float(*offset))
+                }
+            };
+            let enum_name = match signal_enum(msg, signal).as_deref() {
+                Some(Type::Enum { name, .. }) => name.clone(),
+                _ => "None".to_owned(),
+            };
+            writeln!(
+                out,
+                "            Signal(\"{}\", {}, {}, {signed}, {scale}, {offset}, {enum_name}),",
+                signal.name(),
+                signal.byte_offset(),
+                signal.size()
+            )
+            .unwrap();
+        }
+        writeln!(out, "        ),").unwrap();
+        writeln!(out, "    ),").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    out.push_str(POSTLUDE);
+    out
+}
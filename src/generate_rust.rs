@@ -0,0 +1,370 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use canzero_config::config::{NetworkRef, SignalRef, SignalType, Type};
+
+use crate::{
+    decode::signal_enum,
+    errors::{Error, Result},
+};
+
+const KEYWORDS: [&'static str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            let rest: String = chars.collect();
+            if part.chars().all(|c| !c.is_ascii_lowercase()) {
+                format!("{first}{}", rest.to_ascii_lowercase())
+            } else {
+                format!("{first}{rest}")
+            }
+        })
+        .collect()
+}
+
+/// distinct names of a scope, which map to the same identifier, would not compile.
+fn check_collisions<'a>(scope: &str, names: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    let mut idents: BTreeMap<String, &str> = BTreeMap::new();
+    for name in names {
+        let ident = to_pascal_case(name);
+        match idents.get(&ident) {
+            Some(other) if *other != name => {
+                return Err(Error::InvalidConfig(format!(
+                    "{other} and {name} of {scope} both map to the rust identifier {ident}"
+                )))
+            }
+            _ => {
+                idents.insert(ident, name);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_identifiers(network: &NetworkRef) -> Result<()> {
+    let mut types = vec![];
+    for ty in network.types() {
+        if let Type::Enum { name, entries, .. } = &**ty {
+            check_collisions(name, entries.iter().map(|(entry, _)| entry.as_str()))?;
+            types.push(name.as_str());
+        }
+    }
+    types.extend(network.messages().iter().map(|msg| msg.name()));
+    check_collisions("the network", types)?;
+    check_collisions(
+        "the object entries",
+        network
+            .nodes()
+            .iter()
+            .filter(|node| !node.object_entries().is_empty())
+            .map(|node| node.name()),
+    )?;
+    for node in network.nodes() {
+        check_collisions(
+            node.name(),
+            node.object_entries().iter().map(|oe| oe.name()),
+        )?;
+    }
+    Ok(())
+}
+
+fn field_name(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_owned()
+    }
+}
+
+fn int_type(signed: bool, size: u8) -> String {
+    let bits = match size {
+        0..=8 => 8,
+        9..=16 => 16,
+        17..=32 => 32,
+        _ => 64,
+    };
+    if signed {
+        format!("i{bits}")
+    } else {
+        format!("u{bits}")
+    }
+}
+
+struct Field {
+    name: String,
+    ty: String,
+    signal: SignalRef,
+    enum_name: Option<String>,
+}
+
+fn write_enum(out: &mut String, name: &str, entries: &[(String, u64)]) {
+    let name = to_pascal_case(name);
+    writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(out, "pub enum {name} {{").unwrap();
+    for (entry_name, value) in entries {
+        writeln!(out, "    {} = {value},", to_pascal_case(entry_name)).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+    writeln!(out, "impl {name} {{").unwrap();
+    writeln!(out, "    pub fn from_raw(raw: u64) -> Option<Self> {{").unwrap();
+    writeln!(out, "        match raw {{").unwrap();
+    for (entry_name, value) in entries {
+        writeln!(
+            out,
+            "            {value} => Some({name}::{}),",
+            to_pascal_case(entry_name)
+        )
+        .unwrap();
+    }
+    writeln!(out, "            _ => None,").unwrap();
+    writeln!(out, "        }}").unwrap();
+    writeln!(out, "    }}\n").unwrap();
+    writeln!(out, "    pub fn to_raw(self) -> u64 {{").unwrap();
+    if entries.is_empty() {
+        // an enum without variants can't be cast.
+        writeln!(out, "        match self {{}}").unwrap();
+    } else {
+        writeln!(out, "        self as u64").unwrap();
+    }
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}\n").unwrap();
+}
+
+fn decode_expr(field: &Field) -> String {
+    let signal = &field.signal;
+    let size = signal.size() as u32;
+    let raw = format!(
+        "((data >> {}) & 0x{:X}u64)",
+        signal.byte_offset(),
+        0xFFFFFFFFFFFFFFFFu64.overflowing_shr(64 - size).0
+    );
+    if let Some(enum_name) = &field.enum_name {
+        return format!("{enum_name}::from_raw({raw})?");
+    }
+    match signal.ty() {
+        SignalType::UnsignedInt { .. } => format!("{raw} as {}", field.ty),
+        SignalType::SignedInt { .. } => {
+            let shift = 64 - size;
+            format!("((({raw} << {shift}) as i64) >> {shift}) as {}", field.ty)
+        }
+        SignalType::Decimal { offset, scale, .. } => {
+            format!("{raw} as f64 * {scale:?} + {offset:?}")
+        }
+    }
+}
+
+fn encode_expr(field: &Field) -> String {
+    let signal = &field.signal;
+    let mask = 0xFFFFFFFFFFFFFFFFu64
+        .overflowing_shr(64 - signal.size() as u32)
+        .0;
+    let value = format!("self.{}", field.name);
+    let raw = if field.enum_name.is_some() {
+        format!("{value}.to_raw()")
+    } else {
+        match signal.ty() {
+            SignalType::UnsignedInt { .. } => format!("{value} as u64"),
+            SignalType::SignedInt { .. } => format!("{value} as i64 as u64"),
+            SignalType::Decimal { offset, scale, .. } => {
+                format!("(({value} - {offset:?}) / {scale:?}).round() as u64")
+            }
+        }
+    };
+    format!("(({raw}) & 0x{mask:X}u64) << {}", signal.byte_offset())
+}
+
+/// renders a rust module with a struct per message, enums and object entry ids.
+pub fn generate_rust(network: &NetworkRef) -> Result<String> {
+    check_identifiers(network)?;
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// generated by canzero-cli, do not edit!\n#![allow(dead_code, clippy::all)]\n"
+    )
+    .unwrap();
+    writeln!(out, "use canzero_common::CanFrame;\n").unwrap();
+    writeln!(
+        out,
+        "/// portable hash of the network config.\npub const PORTABLE_HASH: u64 = {};\n",
+        network.portable_hash()
+    )
+    .unwrap();
+
+    writeln!(out, "pub mod bus {{").unwrap();
+    for bus in network.buses() {
+        writeln!(
+            out,
+            "    pub const {}: u32 = {};",
+            bus.name().to_ascii_uppercase(),
+            bus.id()
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    for ty in network.types() {
+        if let Type::Enum { name, entries, .. } = &**ty {
+            write_enum(&mut out, name, entries);
+        }
+    }
+
+    for msg in network.messages() {
+        let struct_name = to_pascal_case(msg.name());
+        let fields: Vec<Field> = msg
+            .signals()
+            .iter()
+            .map(|signal| {
+                let enum_name = match signal_enum(msg, signal).as_deref() {
+                    Some(Type::Enum { name, .. }) => Some(to_pascal_case(name)),
+                    _ => None,
+                };
+                let ty = match (&enum_name, signal.ty()) {
+                    (Some(enum_name), _) => enum_name.clone(),
+                    (None, SignalType::UnsignedInt { size }) => int_type(false, *size),
+                    (None, SignalType::SignedInt { size }) => int_type(true, *size),
+                    (None, SignalType::Decimal { .. }) => "f64".to_owned(),
+                };
+                Field {
+                    name: field_name(signal.name()),
+                    ty,
+                    signal: signal.clone(),
+                    enum_name,
+                }
+            })
+            .collect();
+
+        writeln!(out, "/// {} on bus {}", msg.name(), msg.bus().name()).unwrap();
+        writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq)]").unwrap();
+        writeln!(out, "pub struct {struct_name} {{").unwrap();
+        for field in &fields {
+            writeln!(out, "    pub {}: {},", field.name, field.ty).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+
+        writeln!(out, "impl {struct_name} {{").unwrap();
+        writeln!(
+            out,
+            "    pub const NAME: &'static str = \"{}\";",
+            msg.name()
+        )
+        .unwrap();
+        writeln!(out, "    pub const ID: u32 = 0x{:X};", msg.id().as_u32()).unwrap();
+        writeln!(out, "    pub const IDE: bool = {};", msg.id().ide()).unwrap();
+        writeln!(out, "    pub const DLC: u8 = {};", msg.dlc()).unwrap();
+        writeln!(out, "    pub const BUS_ID: u32 = {};\n", msg.bus().id()).unwrap();
+
+        writeln!(
+            out,
+            "    pub fn decode(frame: &CanFrame) -> Option<Self> {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "        if frame.get_id() != Self::ID || frame.get_ide_flag() != Self::IDE {{"
+        )
+        .unwrap();
+        writeln!(out, "            return None;").unwrap();
+        writeln!(out, "        }}").unwrap();
+        if fields.is_empty() {
+            writeln!(out, "        Some(Self {{}})").unwrap();
+        } else {
+            writeln!(out, "        let data = frame.get_data_u64();").unwrap();
+            writeln!(out, "        Some(Self {{").unwrap();
+            for field in &fields {
+                writeln!(out, "            {}: {},", field.name, decode_expr(field)).unwrap();
+            }
+            writeln!(out, "        }})").unwrap();
+        }
+        writeln!(out, "    }}\n").unwrap();
+
+        writeln!(out, "    pub fn encode(&self) -> CanFrame {{").unwrap();
+        if fields.is_empty() {
+            writeln!(out, "        let data = 0;").unwrap();
+        } else {
+            writeln!(out, "        let mut data: u64 = 0;").unwrap();
+        }
+        for field in &fields {
+            writeln!(out, "        data |= {};", encode_expr(field)).unwrap();
+        }
+        writeln!(
+            out,
+            "        CanFrame::new(Self::ID, Self::IDE, false, Self::DLC, data)"
+        )
+        .unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}\n").unwrap();
+    }
+
+    writeln!(out, "pub mod object_entries {{").unwrap();
+    for node in network.nodes() {
+        // an enum without variants can't be cast to its id.
+        if node.object_entries().is_empty() {
+            continue;
+        }
+        writeln!(
+            out,
+            "    /// object entries of node {} (id {})",
+            node.name(),
+            node.id()
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]"
+        )
+        .unwrap();
+        writeln!(out, "    pub enum {} {{", to_pascal_case(node.name())).unwrap();
+        for oe in node.object_entries() {
+            writeln!(out, "        {} = {},", to_pascal_case(oe.name()), oe.id()).unwrap();
+        }
+        writeln!(out, "    }}\n").unwrap();
+        writeln!(out, "    impl {} {{", to_pascal_case(node.name())).unwrap();
+        writeln!(out, "        pub const NODE_ID: u8 = {};\n", node.id()).unwrap();
+        writeln!(out, "        pub fn id(self) -> u32 {{").unwrap();
+        writeln!(out, "            self as u32").unwrap();
+        writeln!(out, "        }}").unwrap();
+        writeln!(out, "    }}\n").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_names_to_pascal_case() {
+        assert_eq!(to_pascal_case("bms_voltage"), "BmsVoltage");
+        assert_eq!(to_pascal_case("STATE__ON"), "StateOn");
+        assert_eq!(to_pascal_case("isCharging"), "IsCharging");
+    }
+
+    #[test]
+    fn rejects_colliding_names() {
+        assert!(check_collisions("state", ["on", "off"]).is_ok());
+        assert!(check_collisions("state", ["on", "ON"]).is_err());
+    }
+
+    #[test]
+    fn empty_enums_match_self() {
+        let mut out = String::new();
+        write_enum(&mut out, "empty", &[]);
+        assert!(out.contains("pub enum Empty {\n}"));
+        assert!(out.contains("match self {}"));
+        assert!(!out.contains("self as u64"));
+
+        let mut out = String::new();
+        write_enum(&mut out, "state", &[("on".to_owned(), 1)]);
+        assert!(out.contains("    On = 1,"));
+        assert!(out.contains("self as u64"));
+    }
+}
@@ -0,0 +1,37 @@
+use std::{ffi::OsStr, path::Path};
+
+use crate::errors::{Error, Result};
+
+/// runs git inside of dir and returns the trimmed stdout.
+pub fn git<I, S>(dir: &Path, args: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+    let Ok(output) = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(&args)
+        .output()
+    else {
+        return Err(Error::MissingDependency("git".to_owned()));
+    };
+    if !output.status.success() {
+        let cmd = args
+            .iter()
+            .map(|a| a.as_ref().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::GitCommandFailed(format!(
+            "git {cmd} ({})",
+            stderr.trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+pub fn toplevel(dir: &Path) -> Result<String> {
+    git(dir, ["rev-parse", "--show-toplevel"])
+}
@@ -0,0 +1,218 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use canzero_appdata::AppData;
+use canzero_config::config::NetworkRef;
+use color_print::cprintln;
+use serde::Serialize;
+
+use crate::{
+    busload::bus_load,
+    config::{config_path, message_receivers, network_config},
+    errors::{Error, Result},
+};
+
+/// object entries which have to be defined by every node,
+/// because other commands (e.g. status) depend on them.
+const REQUIRED_OBJECT_ENTRIES: [&'static str; 2] = ["config_hash", "build_time"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    config_path: Option<PathBuf>,
+    portable_hash: u64,
+    findings: &'a [Finding],
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn lint_ids(network: &NetworkRef, findings: &mut Vec<Finding>) {
+    let mut ids: BTreeMap<String, Vec<(&str, &str)>> = BTreeMap::new();
+    for msg in network.messages() {
+        ids.entry(msg.id().to_string())
+            .or_default()
+            .push((msg.name(), msg.bus().name()));
+    }
+    for (id, users) in ids {
+        if users.len() < 2 {
+            continue;
+        }
+        let mut buses: Vec<&str> = users.iter().map(|(_, bus)| *bus).collect();
+        buses.sort();
+        buses.dedup();
+        let names = users
+            .iter()
+            .map(|(name, bus)| format!("{name} ({bus})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if buses.len() == users.len() {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                rule: "id-collision",
+                message: format!("id {id} is used on multiple buses by {names}"),
+            });
+        } else {
+            findings.push(Finding {
+                severity: Severity::Error,
+                rule: "duplicate-id",
+                message: format!("id {id} is used multiple times on the same bus by {names}"),
+            });
+        }
+    }
+}
+
+fn lint_bus_load(network: &NetworkRef, max_bus_load: f64, findings: &mut Vec<Finding>) {
+    for bus in network.buses() {
+        let load = bus_load(network, bus);
+        if load > max_bus_load {
+            findings.push(Finding {
+                severity: if load > 1.0 {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                },
+                rule: "bus-load",
+                message: format!(
+                    "estimated load of bus {} is {:.1}% (threshold {:.1}%)",
+                    bus.name(),
+                    load * 100.0,
+                    max_bus_load * 100.0
+                ),
+            });
+        }
+    }
+}
+
+fn lint_receivers(network: &NetworkRef, findings: &mut Vec<Finding>) {
+    let get_resp = network.get_resp_message();
+    for msg in network.messages() {
+        // responses are only consumed by the server.
+        if msg.name() == get_resp.name() {
+            continue;
+        }
+        if message_receivers(network, msg).is_empty() {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                rule: "no-receiver",
+                message: format!("message {} is not received by any node", msg.name()),
+            });
+        }
+    }
+}
+
+fn lint_required_object_entries(network: &NetworkRef, findings: &mut Vec<Finding>) {
+    for node in network.nodes() {
+        for required in REQUIRED_OBJECT_ENTRIES {
+            if !node.object_entries().iter().any(|oe| oe.name() == required) {
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    rule: "missing-object-entry",
+                    message: format!("node {} does not define {required}", node.name()),
+                });
+            }
+        }
+    }
+}
+
+fn lint_naming(network: &NetworkRef, findings: &mut Vec<Finding>) {
+    let mut check = |kind: &str, name: &str| {
+        if !is_snake_case(name) {
+            findings.push(Finding {
+                severity: Severity::Info,
+                rule: "naming",
+                message: format!("{kind} {name} is not snake_case"),
+            });
+        }
+    };
+    for node in network.nodes() {
+        check("node", node.name());
+        for oe in node.object_entries() {
+            check("object entry", oe.name());
+        }
+    }
+    for msg in network.messages() {
+        check("message", msg.name());
+        for signal in msg.signals() {
+            check("signal", signal.name());
+        }
+    }
+}
+
+pub fn lint(network: &NetworkRef, max_bus_load: f64) -> Vec<Finding> {
+    let mut findings = vec![];
+    lint_ids(network, &mut findings);
+    lint_bus_load(network, max_bus_load, &mut findings);
+    lint_receivers(network, &mut findings);
+    lint_required_object_entries(network, &mut findings);
+    lint_naming(network, &mut findings);
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity));
+    findings
+}
+
+pub fn command_config_check(max_bus_load: f64, json: Option<PathBuf>) -> Result<()> {
+    let appdata = AppData::read()?;
+    let network = network_config(&appdata)?;
+
+    let findings = lint(&network, max_bus_load / 100.0);
+
+    for finding in &findings {
+        match finding.severity {
+            Severity::Error => {
+                cprintln!("<red>error</red>   [{}] {}", finding.rule, finding.message)
+            }
+            Severity::Warning => {
+                cprintln!(
+                    "<yellow>warning</yellow> [{}] {}",
+                    finding.rule,
+                    finding.message
+                )
+            }
+            Severity::Info => cprintln!(
+                "<blue>info</blue>    [{}] {}",
+                finding.rule,
+                finding.message
+            ),
+        }
+    }
+
+    if let Some(json_path) = json {
+        let report = Report {
+            config_path: config_path(&appdata),
+            portable_hash: network.portable_hash(),
+            findings: &findings,
+        };
+        let file = std::fs::File::create(json_path)?;
+        serde_json::to_writer_pretty(file, &report)
+            .map_err(|err| Error::Io(std::io::Error::from(err)))?;
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    if errors != 0 {
+        return Err(Error::ConfigCheckFailed(errors));
+    }
+    if findings.is_empty() {
+        cprintln!("<green>No issues found</green>");
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use canzero_appdata::AppData;
+use canzero_config::config::{MessageUsage, NetworkRef, SignalRef};
+use color_print::cprintln;
+
+use crate::{
+    config::{message_sender, network_config},
+    decode::{flatten_object_entry, frame_data, signal_value},
+    dump::connect,
+    errors::Result,
+};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// a csv file in the layout of example-log, e.g.
+/// timestamp; foo.x; foo.y
+struct CsvLog {
+    path: PathBuf,
+    columns: Vec<(String, SignalRef)>,
+    writer: Option<BufWriter<File>>,
+}
+
+impl CsvLog {
+    fn new(path: PathBuf, columns: Vec<(String, SignalRef)>) -> Self {
+        CsvLog {
+            path,
+            columns,
+            writer: None,
+        }
+    }
+
+    /// files are only created once the first value is received.
+    fn writer(&mut self) -> Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            std::fs::create_dir_all(self.path.parent().unwrap())?;
+            let mut writer = BufWriter::new(File::create(&self.path)?);
+            write!(writer, "timestamp")?;
+            for (name, _) in &self.columns {
+                write!(writer, "; {name}")?;
+            }
+            writeln!(writer)?;
+            self.writer = Some(writer);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    fn write(&mut self, timestamp: Duration, data: u64) -> Result<()> {
+        let values: Vec<f64> = self
+            .columns
+            .iter()
+            .map(|(_, signal)| signal_value(data, signal))
+            .collect();
+        let writer = self.writer()?;
+        write!(writer, "{}", timestamp.as_micros())?;
+        for value in values {
+            write!(writer, "; {value}")?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// the file names of the logs by (sender, name, message name). Object entries, which are
+/// streamed by multiple messages, e.g. on different buses, are suffixed with the message name.
+fn log_file_names(logs: &[(String, String, String)]) -> Vec<PathBuf> {
+    let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+    for (sender, name, _) in logs {
+        *counts.entry((sender.as_str(), name.as_str())).or_default() += 1;
+    }
+    logs.iter()
+        .map(|(sender, name, msg_name)| {
+            if counts[&(sender.as_str(), name.as_str())] > 1 {
+                Path::new(sender).join(format!("{name}.{msg_name}.csv"))
+            } else {
+                Path::new(sender).join(format!("{name}.csv"))
+            }
+        })
+        .collect()
+}
+
+/// creates the csv logs of all messages, indexed by (bus id, id, ide).
+fn create_logs(
+    network: &NetworkRef,
+    dir: &Path,
+) -> (Vec<CsvLog>, HashMap<(u32, u32, bool), Vec<usize>>) {
+    let mut names = vec![];
+    let mut logs = vec![];
+    let mut index: HashMap<(u32, u32, bool), Vec<usize>> = HashMap::new();
+    let get_req = network.get_req_message();
+    let get_resp = network.get_resp_message();
+    for msg in network.messages() {
+        if msg.name() == get_req.name() || msg.name() == get_resp.name() {
+            continue;
+        }
+        let key = (msg.bus().id(), msg.id().as_u32(), msg.id().ide());
+        let sender =
+            message_sender(network, msg).map_or("unknown".to_owned(), |n| n.name().to_owned());
+        match (msg.usage(), msg.encoding()) {
+            // object entries
+            (MessageUsage::Stream(stream), Some(encoding)) => {
+                for (oe, oe_encoding) in stream.mapping().iter().zip(encoding.attributes()) {
+                    let Some(oe) = oe else {
+                        continue;
+                    };
+                    names.push((sender.clone(), oe.name().to_owned(), msg.name().to_owned()));
+                    index.entry(key).or_default().push(logs.len());
+                    logs.push(flatten_object_entry(oe_encoding));
+                }
+            }
+            // plain signals
+            _ => {
+                if msg.signals().is_empty() {
+                    continue;
+                }
+                let columns = msg
+                    .signals()
+                    .iter()
+                    .map(|s| (s.name().to_owned(), s.clone()))
+                    .collect();
+                names.push((sender, msg.name().to_owned(), msg.name().to_owned()));
+                index.entry(key).or_default().push(logs.len());
+                logs.push(columns);
+            }
+        }
+    }
+    let logs = log_file_names(&names)
+        .into_iter()
+        .zip(logs)
+        .map(|(file_name, columns)| CsvLog::new(dir.join(file_name), columns))
+        .collect();
+    (logs, index)
+}
+
+pub async fn command_log(dir: PathBuf) -> Result<()> {
+    let appdata = AppData::read()?;
+    let network_config = network_config(&appdata)?;
+
+    let (mut logs, index) = create_logs(&network_config, &dir);
+
+    let (_, tcpcan) = connect().await?;
+    cprintln!("<green>Logging to {dir:?}, stop with Ctrl-C</green>");
+
+    let mut flush_interval = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            frame = tcpcan.recv() => {
+                let Some(frame) = frame else {
+                    println!("Connection closed");
+                    break;
+                };
+                let can_frame = &frame.can_frame;
+                let key = (frame.bus_id, can_frame.get_id(), can_frame.get_ide_flag());
+                let Some(log_indices) = index.get(&key) else {
+                    continue;
+                };
+                let data = frame_data(can_frame.get_data_u64(), can_frame.get_dlc());
+                for i in log_indices {
+                    logs[*i].write(frame.timestamp, data)?;
+                }
+            }
+            _ = flush_interval.tick() => {
+                for log in &mut logs {
+                    log.flush()?;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    for log in &mut logs {
+        log.flush()?;
+    }
+    let written = logs.iter().filter(|l| l.writer.is_some()).count();
+    println!("Wrote {written} csv files to {dir:?}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(logs: &[(&str, &str, &str)]) -> Vec<(String, String, String)> {
+        logs.iter()
+            .map(|(sender, name, msg)| (sender.to_string(), name.to_string(), msg.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn suffixes_colliding_file_names() {
+        let logs = names(&[
+            ("mother_board", "position", "mother_board_stream_can0"),
+            ("mother_board", "position", "mother_board_stream_can1"),
+            ("mother_board", "velocity", "mother_board_stream_can0"),
+            ("guidance", "position", "guidance_stream"),
+        ]);
+        assert_eq!(
+            log_file_names(&logs),
+            [
+                PathBuf::from("mother_board/position.mother_board_stream_can0.csv"),
+                PathBuf::from("mother_board/position.mother_board_stream_can1.csv"),
+                PathBuf::from("mother_board/velocity.csv"),
+                PathBuf::from("guidance/position.csv"),
+            ]
+        );
+    }
+}
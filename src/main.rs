@@ -1,36 +1,73 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use build_time::build_time_local;
 use clap::{Parser, Subcommand};
 use config::command_config_get;
 
 use crate::{
+    busload::command_config_busload,
     client::command_client,
     config::{
-        command_config_check, command_config_hash, command_config_messages_list,
-        command_config_nodes_list, command_config_object_entries_list, command_config_set,
-        command_config_show,
+        command_config_hash, command_config_messages_list, command_config_nodes_list,
+        command_config_object_entries_list, command_config_set, command_config_show,
+        set_config_override,
     },
-    dump::command_dump,
+    config_repo::{command_config_pin, command_config_pull},
+    convert::command_convert,
+    diff::command_config_diff,
+    dump::{command_dump, parse_time, DumpOptions, TimeFormat},
     errors::Error,
-    generate::command_generate,
+    export::{command_export, ExportFormat},
+    expr::{parse_expr, Expr},
+    generate::{command_generate, GenerateMode, GenerateTarget, Language, NodeOptions},
+    lint::command_config_check,
+    log::command_log,
+    monitor::command_monitor,
+    plot::command_plot,
+    profiles::{
+        command_config_add, command_config_list, command_config_remove, command_config_use,
+    },
     scan::command_scan,
     server::command_server,
     ssh::{command_ssh, command_ssh_reboot},
+    stats::command_stats,
     status::command_status,
+    trace::TraceFormat,
     update::{command_update_self, command_update_server},
 };
 
+mod asc;
+mod blf;
+mod busload;
 mod client;
 mod config;
+mod config_repo;
+mod convert;
+mod decode;
+mod diff;
 mod dump;
 mod errors;
+mod expr;
+mod export;
 mod generate;
+mod generate_python;
+mod generate_rust;
 mod get;
+mod git;
+mod lint;
+mod log;
+mod mdf;
+mod monitor;
+mod pcap;
+mod plot;
+mod profiles;
 mod scan;
 mod server;
 mod ssh;
+mod stats;
 mod status;
+mod trace;
+mod trigger;
 mod update;
 
 #[derive(Parser, Debug)]
@@ -40,6 +77,12 @@ mod update;
     arg_required_else_help = true
 )]
 struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Name of a config profile or path to a network configuration, used instead of the selected one."
+    )]
+    config: Option<String>,
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -57,11 +100,63 @@ enum Command {
     #[clap(alias = "gen")]
     #[command(about = "Generate c code from the selected network configuration.")]
     Generate {
-        node_name: String,
-        output_dir: PathBuf,
+        node_name: Option<String>,
+        output_dir: Option<PathBuf>,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = Language::C,
+            help = "Language of the generated code, all languages except c generate a single module to --output."
+        )]
+        lang: Language,
+        #[arg(
+            long,
+            short,
+            conflicts_with_all = ["node_name", "output_dir", "all", "mapping"],
+            help = "Output file of the module of --lang python or rust."
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            long,
+            value_name = "OUT_ROOT",
+            conflicts_with_all = ["node_name", "output_dir", "mapping"],
+            help = "Generate all nodes into subdirectories of OUT_ROOT."
+        )]
+        all: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with_all = ["node_name", "output_dir"],
+            help = "Generate the nodes listed in a yaml file mapping node names to output directories \
+                    or to their options (output_dir, source_file, header_file, namespace, header_guard)."
+        )]
+        mapping: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with = "dry_run",
+            help = "Fail if the generated files differ from what the current config would produce."
+        )]
+        check: bool,
+        #[arg(long, help = "Print a diff of the changes instead of writing the files.")]
+        dry_run: bool,
+        #[arg(long, help = "Create missing output directories.")]
+        create_dirs: bool,
+        #[arg(long, help = "Name of the generated source file (default canzero.cpp).")]
+        source_file: Option<String>,
+        #[arg(long, help = "Name of the generated header file (default canzero.h).")]
+        header_file: Option<String>,
+        #[arg(long, help = "Namespace/prefix of the generated code.")]
+        namespace: Option<String>,
+        #[arg(long, help = "Header guard of the generated header file.")]
+        header_guard: Option<String>,
+        #[arg(long, help = "Regenerate whenever a file of the network config changes.")]
+        watch: bool,
     },
-    #[command(about = "Start canzero graphical user interface.")]
+    #[command(about = "Start canzero graphical user interface, the terminal monitor in the standalone cli.")]
     Gui,
+    #[command(
+        about = "Show the latest values, count, rate and age of every message of the live trace."
+    )]
+    Monitor,
     #[command(about = "Interact with or start the server node.")]
     Server {
         #[command(subcommand)]
@@ -72,7 +167,7 @@ enum Command {
         #[command(subcommand)]
         command: ClientCommand,
     },
-    #[command(about = "Print the CAN trace to the control.")]
+    #[command(about = "Print the CAN trace of the server or of a recorded trace file.")]
     Dump {
         #[clap(alias = "msg")]
         #[arg(short, long, num_args=0..)]
@@ -80,9 +175,151 @@ enum Command {
         #[clap(alias = "id")]
         #[arg(short, long, num_args=0..)]
         ids: Vec<String>,
+        #[arg(long, help = "Record all frames into a CANzero trace (json lines).")]
+        record: Option<PathBuf>,
+        #[arg(
+            long,
+            help = "Write all frames into a pcapng capture with one SocketCAN interface per bus."
+        )]
+        pcap: Option<PathBuf>,
+        #[arg(
+            long,
+            conflicts_with_all = ["record", "pcap"],
+            help = "Print a recorded trace (any format of convert) instead of the live trace."
+        )]
+        file: Option<PathBuf>,
+        #[arg(
+            long,
+            requires = "file",
+            value_parser = parse_time,
+            help = "Skip frames before this time, e.g. 12.5s."
+        )]
+        from: Option<Duration>,
+        #[arg(
+            long,
+            requires = "file",
+            value_parser = parse_time,
+            help = "Skip frames after this time, e.g. 30s."
+        )]
+        to: Option<Duration>,
+        #[arg(
+            long = "where",
+            value_parser = parse_expr,
+            help = "Only print frames matching the expression, e.g. 'bms.voltage < 300 && node == mother_board'."
+        )]
+        filter: Option<Expr>,
+        #[arg(
+            long,
+            help = "Only print frames, which change a signal value of their message."
+        )]
+        changed: bool,
+        #[arg(
+            short,
+            long,
+            help = "Annotate frames with their sender and receivers and flag frames on the wrong bus."
+        )]
+        verbose: bool,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = TimeFormat::Relative,
+            help = "Timestamps relative to the server timebase, as UTC wall-clock time or relative to the previous frame."
+        )]
+        time: TimeFormat,
+        #[arg(
+            long,
+            value_parser = parse_expr,
+            help = "Save the frames around every frame matching the expression, e.g. 'state_machine.state == EMERGENCY' or id=0x123."
+        )]
+        trigger: Option<Expr>,
+        #[arg(
+            long,
+            requires = "trigger",
+            value_parser = parse_time,
+            default_value = "5s",
+            help = "Time saved before a trigger."
+        )]
+        pre: Duration,
+        #[arg(
+            long,
+            requires = "trigger",
+            value_parser = parse_time,
+            default_value = "5s",
+            help = "Time saved after a trigger."
+        )]
+        post: Duration,
+        #[arg(
+            long,
+            requires = "trigger",
+            default_value = ".",
+            help = "Directory of the saved trigger windows."
+        )]
+        trigger_dir: PathBuf,
     },
     #[command(about = "Check the status of all connected nodes.")]
     Status,
+    #[command(
+        about = "Log object entries and signals into one csv file per node and entry.",
+        arg_required_else_help = true
+    )]
+    Log { dir: PathBuf },
+    #[command(
+        about = "Plot logged object entries of a node, one subplot per value.",
+        arg_required_else_help = true
+    )]
+    Plot {
+        log_dir: PathBuf,
+        node: String,
+        #[arg(required = true)]
+        entries: Vec<String>,
+        #[arg(
+            short,
+            long,
+            default_value = "plot.svg",
+            help = "Output file, the format (svg or png) is chosen by its extension."
+        )]
+        output: PathBuf,
+    },
+    #[command(
+        about = "Export a recorded trace to mf4, asc or blf.",
+        arg_required_else_help = true
+    )]
+    Export {
+        trace: PathBuf,
+        output: PathBuf,
+        #[arg(
+            short,
+            long,
+            value_enum,
+            help = "Format of the output file, defaults to the format of its extension."
+        )]
+        format: Option<ExportFormat>,
+        #[arg(long, help = "Include the decoded signals of all messages (mf4 only).")]
+        decode: bool,
+    },
+    #[command(
+        about = "Convert a trace between candump, CANzero json lines, csv, pcap and asc.",
+        arg_required_else_help = true
+    )]
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        #[arg(
+            short,
+            long,
+            value_enum,
+            help = "Format of the output file, defaults to the format of its extension. The input format is detected from its content."
+        )]
+        format: Option<TraceFormat>,
+    },
+    #[command(
+        about = "Report frame counts, timing, bus load and unknown ids of a trace file or of the live trace.",
+        arg_required_else_help = true
+    )]
+    Stats {
+        #[arg(help = "Path of a trace file or \"live\" to collect statistics until Ctrl-C.")]
+        source: String,
+    },
     #[command(about = "Update CANzero.")]
     Update {
         #[arg(short='s', long="socketcan", action = clap::ArgAction::SetTrue)]
@@ -94,10 +331,31 @@ enum Command {
 #[derive(Subcommand, Debug)]
 enum ConfigCommand {
     #[command(
-        about = "Set path to network configuration.",
+        about = "Set path or git repository of the network configuration.",
+        arg_required_else_help = true
+    )]
+    Set {
+        path: String,
+        #[arg(short, long, help = "Branch, tag or commit of the git repository.")]
+        rev: Option<String>,
+        #[arg(short, long, help = "Path of the main config file within the git repository.")]
+        file: Option<PathBuf>,
+    },
+    #[command(about = "Update the network configuration from its git repository.")]
+    Pull,
+    #[command(about = "Pin the network configuration to a git revision.")]
+    Pin { rev: Option<String> },
+    #[command(
+        about = "Add a named config profile, defaults to the current configuration.",
         arg_required_else_help = true
     )]
-    Set { path: PathBuf },
+    Add { name: String, path: Option<PathBuf> },
+    #[command(about = "Remove a named config profile.", arg_required_else_help = true)]
+    Remove { name: String },
+    #[command(about = "Select the network configuration of a profile.", arg_required_else_help = true)]
+    Use { name: String },
+    #[command(about = "List all config profiles.")]
+    List,
     #[command(
         about = "Display the network configuration.",
         arg_required_else_help = false
@@ -110,8 +368,23 @@ enum ConfigCommand {
         about = "Check the network configuration for errors.",
         arg_required_else_help = false
     )]
-    Check,
+    Check {
+        #[arg(long, default_value_t = 70.0, help = "Bus load in percent above which a bus is reported.")]
+        max_bus_load: f64,
+        #[arg(long, help = "Write the findings as a JSON report to the given path.")]
+        json: Option<PathBuf>,
+    },
     Where,
+    #[command(
+        about = "Compare the network configuration with another config file or git revision.",
+        arg_required_else_help = true
+    )]
+    Diff { other: String },
+    #[command(about = "Estimate the bus load of all buses in the network configuration.")]
+    Busload {
+        #[arg(short, long)]
+        bus: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -148,10 +421,22 @@ enum ClientCommand {
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    if let Some(config) = &cli.config {
+        if let Err(err) = set_config_override(config) {
+            eprintln!("{err:?}");
+            std::process::exit(1);
+        }
+    }
     let res = match cli.command {
         Some(cmd) => match cmd {
             Command::Config { command } => match command {
-                ConfigCommand::Set { path } => command_config_set(path),
+                ConfigCommand::Set { path, rev, file } => command_config_set(path, rev, file),
+                ConfigCommand::Pull => command_config_pull(),
+                ConfigCommand::Pin { rev } => command_config_pin(rev),
+                ConfigCommand::Add { name, path } => command_config_add(name, path),
+                ConfigCommand::Remove { name } => command_config_remove(name),
+                ConfigCommand::Use { name } => command_config_use(name),
+                ConfigCommand::List => command_config_list(),
                 ConfigCommand::Show { command } => match command {
                     Some(config_show_command) => match config_show_command {
                         ConfigShowCommand::Hash => command_config_hash(),
@@ -165,14 +450,92 @@ async fn main() {
                     },
                     None => command_config_show(),
                 },
-                ConfigCommand::Check => command_config_check(),
+                ConfigCommand::Check { max_bus_load, json } => {
+                    command_config_check(max_bus_load, json)
+                }
                 ConfigCommand::Where => command_config_get(),
+                ConfigCommand::Diff { other } => command_config_diff(other),
+                ConfigCommand::Busload { bus } => command_config_busload(bus),
             },
             Command::Generate {
                 node_name,
                 output_dir,
-            } => command_generate(&node_name, &output_dir),
-            Command::Gui => Err(Error::NotYetImplemented),
+                lang,
+                output,
+                all,
+                mapping,
+                check,
+                dry_run,
+                create_dirs,
+                source_file,
+                header_file,
+                namespace,
+                header_guard,
+                watch,
+            } => {
+                let options = NodeOptions {
+                    output_dir: None,
+                    source_file,
+                    header_file,
+                    namespace,
+                    header_guard,
+                };
+                let target = match lang {
+                    Language::C => match (all, mapping, node_name, output_dir) {
+                        _ if output.is_some() => Err(Error::UnsupportedArgument(
+                            "--output of --lang c, use <OUTPUT_DIR>".to_owned(),
+                        )),
+                        (Some(output_root), _, _, _) => Ok(GenerateTarget::All(output_root)),
+                        (None, Some(mapping), _, _) => Ok(GenerateTarget::Mapping(mapping)),
+                        (None, None, Some(node_name), Some(output_dir)) => {
+                            Ok(GenerateTarget::Node {
+                                node_name,
+                                output_dir,
+                            })
+                        }
+                        (None, None, None, _) => {
+                            Err(Error::MissingArgument("<NODE_NAME>".to_owned()))
+                        }
+                        (None, None, Some(_), None) => {
+                            Err(Error::MissingArgument("<OUTPUT_DIR>".to_owned()))
+                        }
+                    },
+                    language => {
+                        // the other languages generate a single module for the whole network.
+                        let node_args = [
+                            ("<NODE_NAME>", node_name.is_some()),
+                            ("<OUTPUT_DIR>", output_dir.is_some()),
+                            ("--all", all.is_some()),
+                            ("--mapping", mapping.is_some()),
+                            ("--source-file", options.source_file.is_some()),
+                            ("--header-file", options.header_file.is_some()),
+                            ("--namespace", options.namespace.is_some()),
+                            ("--header-guard", options.header_guard.is_some()),
+                        ];
+                        match (node_args.iter().find(|(_, given)| *given), output) {
+                            (Some((arg, _)), _) => Err(Error::UnsupportedArgument(format!(
+                                "{arg} of --lang {language:?}"
+                            ))),
+                            (None, Some(output)) => {
+                                Ok(GenerateTarget::Network { language, output })
+                            }
+                            (None, None) => Err(Error::MissingArgument("--output".to_owned())),
+                        }
+                    }
+                };
+                let mode = match (check, dry_run) {
+                    (true, _) => GenerateMode::Check,
+                    (false, true) => GenerateMode::DryRun,
+                    (false, false) => GenerateMode::Write,
+                };
+                target.and_then(|target| {
+                    command_generate(target, options, mode, create_dirs, watch)
+                })
+            }
+            // the desktop gui is started by the host of run_cli,
+            // the standalone cli shows the terminal ui instead.
+            Command::Gui => command_monitor().await,
+            Command::Monitor => command_monitor().await,
             Command::Server { command } => match command {
                 ServerCommand::Start => command_server().await,
                 ServerCommand::Scan => command_scan().await,
@@ -187,8 +550,62 @@ async fn main() {
             Command::Client { command } => match command {
                 ClientCommand::Start => command_client().await,
             },
-            Command::Dump { messages, ids } => command_dump(messages, ids).await,
+            Command::Dump {
+                messages,
+                ids,
+                record,
+                pcap,
+                file,
+                from,
+                to,
+                filter,
+                changed,
+                verbose,
+                time,
+                trigger,
+                pre,
+                post,
+                trigger_dir,
+            } => {
+                command_dump(DumpOptions {
+                    messages,
+                    ids,
+                    record,
+                    pcap,
+                    file,
+                    from,
+                    to,
+                    filter,
+                    changed,
+                    verbose,
+                    time,
+                    trigger,
+                    pre,
+                    post,
+                    trigger_dir,
+                })
+                .await
+            }
             Command::Status => command_status().await,
+            Command::Log { dir } => command_log(dir).await,
+            Command::Plot {
+                log_dir,
+                node,
+                entries,
+                output,
+            } => command_plot(log_dir, node, entries, output),
+            Command::Export {
+                trace,
+                output,
+                format,
+                decode,
+            } => command_export(trace, output, format, decode),
+            Command::Convert {
+                input,
+                output,
+                format,
+            } => command_convert(input, output, format),
+            Command::Stats { source } => command_stats(source).await,
             Command::Update { socketcan } => command_update_self(socketcan),
             Command::Version => {
                 println!("build-time : {}", build_time_local!());
@@ -199,6 +616,8 @@ async fn main() {
     };
     if let Err(err) = res {
         eprintln!("{err:?}");
+        // e.g. generate --check has to fail ci.
+        std::process::exit(1);
     }
 }
 
@@ -0,0 +1,484 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+
+use canzero_config::config::{MessageRef, NetworkRef, SignalType, Type};
+
+use crate::{config::frame_message, decode::signal_enum, trace::TraceFrame};
+
+const ID_BLOCK_SIZE: usize = 64;
+const HD_BLOCK_SIZE: usize = 104;
+
+const CN_TYPE_VALUE: u8 = 0;
+const CN_TYPE_MASTER: u8 = 2;
+const CN_SYNC_NONE: u8 = 0;
+const CN_SYNC_TIME: u8 = 1;
+const DT_UNSIGNED: u8 = 0;
+const DT_SIGNED: u8 = 2;
+const DT_FLOAT: u8 = 4;
+const DT_BYTE_ARRAY: u8 = 10;
+
+const CC_LINEAR: u8 = 1;
+const CC_VALUE_TO_TEXT: u8 = 7;
+
+const CG_FLAG_BUS_EVENT: u16 = 0x02;
+const CG_FLAG_PLAIN_BUS_EVENT: u16 = 0x04;
+
+const SI_TYPE_BUS: u8 = 2;
+const SI_BUS_CAN: u8 = 2;
+
+/// record of a frame in a CAN_DataFrame channel group:
+/// timestamp (f64), bus channel (u8), id (u32, ide in bit 31), dlc (u8),
+/// data length (u8) and the data bytes.
+const CAN_RECORD_SIZE: u32 = 23;
+/// record of a decoded message: timestamp (f64) and the raw frame data (u64),
+/// signals are channels into the frame data.
+const MESSAGE_RECORD_SIZE: u32 = 16;
+
+fn encode_block(id: &[u8; 4], links: &[u64], data: &[u8]) -> Vec<u8> {
+    let length = 24 + links.len() * 8 + data.len();
+    let mut block = Vec::with_capacity(length);
+    block.extend_from_slice(id);
+    block.extend_from_slice(&[0; 4]);
+    block.extend_from_slice(&(length as u64).to_le_bytes());
+    block.extend_from_slice(&(links.len() as u64).to_le_bytes());
+    for link in links {
+        block.extend_from_slice(&link.to_le_bytes());
+    }
+    block.extend_from_slice(data);
+    block
+}
+
+struct Channel {
+    name: String,
+    channel_type: u8,
+    sync_type: u8,
+    data_type: u8,
+    byte_offset: u32,
+    bit_offset: u8,
+    bit_count: u32,
+    unit: Option<&'static str>,
+    conversion: u64,
+    composition: u64,
+}
+
+impl Channel {
+    fn value(name: String, data_type: u8, bit: u32, bit_count: u32) -> Self {
+        Channel {
+            name,
+            channel_type: CN_TYPE_VALUE,
+            sync_type: CN_SYNC_NONE,
+            data_type,
+            byte_offset: bit / 8,
+            bit_offset: (bit % 8) as u8,
+            bit_count,
+            unit: None,
+            conversion: 0,
+            composition: 0,
+        }
+    }
+
+    fn time() -> Self {
+        Channel {
+            name: "Timestamp".to_owned(),
+            channel_type: CN_TYPE_MASTER,
+            sync_type: CN_SYNC_TIME,
+            unit: Some("s"),
+            ..Channel::value(String::new(), DT_FLOAT, 0, 64)
+        }
+    }
+}
+
+/// minimal ASAM MDF 4.10 writer, every channel group gets its own
+/// (sorted, uncompressed) data group.
+struct MdfWriter {
+    buf: Vec<u8>,
+    /// (channel group, data) of all data groups.
+    groups: Vec<(u64, u64)>,
+}
+
+impl MdfWriter {
+    fn new() -> Self {
+        MdfWriter {
+            // the id and header block are written by finish.
+            buf: vec![0; ID_BLOCK_SIZE + HD_BLOCK_SIZE],
+            groups: vec![],
+        }
+    }
+
+    /// appends a block at the next 8 byte aligned offset and returns its offset.
+    fn block(&mut self, id: &[u8; 4], links: &[u64], data: &[u8]) -> u64 {
+        while self.buf.len() % 8 != 0 {
+            self.buf.push(0);
+        }
+        let offset = self.buf.len() as u64;
+        self.buf.extend(encode_block(id, links, data));
+        offset
+    }
+
+    fn text(&mut self, text: &str) -> u64 {
+        let mut data = text.as_bytes().to_vec();
+        data.push(0);
+        self.block(b"##TX", &[], &data)
+    }
+
+    fn linear_conversion(&mut self, offset: f64, scale: f64) -> u64 {
+        let mut data = vec![CC_LINEAR, 0];
+        data.extend_from_slice(&0u16.to_le_bytes()); // flags
+        data.extend_from_slice(&0u16.to_le_bytes()); // ref count
+        data.extend_from_slice(&2u16.to_le_bytes()); // val count
+        data.extend_from_slice(&0f64.to_le_bytes());
+        data.extend_from_slice(&0f64.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(&scale.to_le_bytes());
+        self.block(b"##CC", &[0, 0, 0, 0], &data)
+    }
+
+    fn value_to_text_conversion(&mut self, entries: &[(String, u64)]) -> u64 {
+        let mut links = vec![0, 0, 0, 0];
+        for (name, _) in entries {
+            links.push(self.text(name));
+        }
+        // default text
+        links.push(0);
+        let mut data = vec![CC_VALUE_TO_TEXT, 0];
+        data.extend_from_slice(&0u16.to_le_bytes()); // flags
+        data.extend_from_slice(&((entries.len() + 1) as u16).to_le_bytes());
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        data.extend_from_slice(&0f64.to_le_bytes());
+        data.extend_from_slice(&0f64.to_le_bytes());
+        for (_, value) in entries {
+            data.extend_from_slice(&(*value as f64).to_le_bytes());
+        }
+        self.block(b"##CC", &links, &data)
+    }
+
+    /// writes a linked list of channels and returns the offset of the first.
+    fn channels(&mut self, channels: &[Channel]) -> u64 {
+        let mut next = 0;
+        for channel in channels.iter().rev() {
+            let name = self.text(&channel.name);
+            let unit = match channel.unit {
+                Some(unit) => self.text(unit),
+                None => 0,
+            };
+            let links = [
+                next,
+                channel.composition,
+                name,
+                0,
+                channel.conversion,
+                0,
+                unit,
+                0,
+            ];
+            let mut data = vec![
+                channel.channel_type,
+                channel.sync_type,
+                channel.data_type,
+                channel.bit_offset,
+            ];
+            data.extend_from_slice(&channel.byte_offset.to_le_bytes());
+            data.extend_from_slice(&channel.bit_count.to_le_bytes());
+            data.extend_from_slice(&0u32.to_le_bytes()); // flags
+            data.extend_from_slice(&0u32.to_le_bytes()); // invalidation bit
+            data.extend_from_slice(&[0, 0]); // precision, reserved
+            data.extend_from_slice(&0u16.to_le_bytes()); // attachments
+            data.extend_from_slice(&[0; 6 * 8]); // ranges and limits
+            next = self.block(b"##CN", &links, &data);
+        }
+        next
+    }
+
+    fn bus_source(&mut self, bus_name: &str) -> u64 {
+        let name = self.text(bus_name);
+        self.block(
+            b"##SI",
+            &[name, 0, 0],
+            &[SI_TYPE_BUS, SI_BUS_CAN, 0, 0, 0, 0, 0, 0],
+        )
+    }
+
+    fn group(
+        &mut self,
+        acquisition_name: &str,
+        source: u64,
+        flags: u16,
+        channels: &[Channel],
+        record_size: u32,
+        records: &[u8],
+    ) {
+        let first_channel = self.channels(channels);
+        let acquisition_name = self.text(acquisition_name);
+        let mut data = vec![];
+        data.extend_from_slice(&0u64.to_le_bytes()); // record id
+        data.extend_from_slice(&((records.len() / record_size as usize) as u64).to_le_bytes());
+        data.extend_from_slice(&flags.to_le_bytes());
+        data.extend_from_slice(&(b'.' as u16).to_le_bytes()); // path separator
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&record_size.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes()); // invalidation bytes
+        let channel_group = self.block(
+            b"##CG",
+            &[0, first_channel, acquisition_name, source, 0, 0],
+            &data,
+        );
+        let data = self.block(b"##DT", &[], records);
+        self.groups.push((channel_group, data));
+    }
+
+    fn finish(mut self, start_time_ns: u64) -> Vec<u8> {
+        let mut first_data_group = 0;
+        for (channel_group, data) in std::mem::take(&mut self.groups).into_iter().rev() {
+            first_data_group = self.block(
+                b"##DG",
+                &[first_data_group, channel_group, data, 0],
+                &[0; 8],
+            );
+        }
+
+        let comment = format!(
+            "<FHcomment><TX>exported by canzero-cli</TX><tool_id>canzero</tool_id>\
+             <tool_vendor>mu-zero HYPERLOOP</tool_vendor>\
+             <tool_version>{}</tool_version></FHcomment>",
+            env!("CARGO_PKG_VERSION")
+        );
+        let mut data = comment.into_bytes();
+        data.push(0);
+        let comment = self.block(b"##MD", &[], &data);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        let mut data = now.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0; 8]); // time zone, dst, time flags, reserved
+        let file_history = self.block(b"##FH", &[0, comment], &data);
+
+        let mut data = start_time_ns.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0; 8]); // time zone, dst, flags, reserved
+        data.extend_from_slice(&[0; 16]); // start angle and distance
+        let header = encode_block(
+            b"##HD",
+            &[first_data_group, file_history, 0, 0, 0, 0],
+            &data,
+        );
+        self.buf[ID_BLOCK_SIZE..ID_BLOCK_SIZE + HD_BLOCK_SIZE].copy_from_slice(&header);
+
+        let mut id = vec![];
+        id.extend_from_slice(b"MDF     ");
+        id.extend_from_slice(b"4.10    ");
+        id.extend_from_slice(b"canzero ");
+        id.extend_from_slice(&[0; 4]);
+        id.extend_from_slice(&410u16.to_le_bytes());
+        id.extend_from_slice(&[0; 34]); // reserved and unfinalized flags
+        self.buf[..ID_BLOCK_SIZE].copy_from_slice(&id);
+        self.buf
+    }
+}
+
+fn can_data_frame_channels() -> Vec<Channel> {
+    vec![
+        Channel::value("CAN_DataFrame.BusChannel".to_owned(), DT_UNSIGNED, 64, 8),
+        Channel::value("CAN_DataFrame.ID".to_owned(), DT_UNSIGNED, 72, 29),
+        Channel::value("CAN_DataFrame.IDE".to_owned(), DT_UNSIGNED, 72 + 31, 1),
+        Channel::value("CAN_DataFrame.DLC".to_owned(), DT_UNSIGNED, 104, 4),
+        Channel::value("CAN_DataFrame.DataLength".to_owned(), DT_UNSIGNED, 112, 8),
+        Channel::value("CAN_DataFrame.DataBytes".to_owned(), DT_BYTE_ARRAY, 120, 64),
+    ]
+}
+
+fn can_record(frame: &TraceFrame) -> Vec<u8> {
+    let mut record = Vec::with_capacity(CAN_RECORD_SIZE as usize);
+    record.extend_from_slice(&(frame.timestamp as f64 * 1e-6).to_le_bytes());
+    record.push(frame.bus as u8);
+    record.extend_from_slice(&(frame.id | ((frame.ide as u32) << 31)).to_le_bytes());
+    record.push(frame.dlc);
+    record.push(frame.dlc.min(8));
+    record.extend_from_slice(&frame.data.to_le_bytes());
+    record
+}
+
+fn signal_channels(writer: &mut MdfWriter, msg: &MessageRef) -> Vec<Channel> {
+    let mut channels = vec![Channel::time()];
+    for signal in msg.signals() {
+        let bit = 64 + signal.byte_offset() as u32;
+        let bit_count = signal.size() as u32;
+        let name = format!("{}.{}", msg.name(), signal.name());
+        let mut channel = match signal.ty() {
+            SignalType::UnsignedInt { .. } => Channel::value(name, DT_UNSIGNED, bit, bit_count),
+            SignalType::SignedInt { .. } => Channel::value(name, DT_SIGNED, bit, bit_count),
+            SignalType::Decimal { offset, scale, .. } => {
+                let mut channel = Channel::value(name, DT_UNSIGNED, bit, bit_count);
+                channel.conversion = writer.linear_conversion(*offset, *scale);
+                channel
+            }
+        };
+        if let Some(Type::Enum { entries, .. }) = signal_enum(msg, signal).as_deref() {
+            channel.conversion = writer.value_to_text_conversion(entries);
+        }
+        channels.push(channel);
+    }
+    channels
+}
+
+/// renders the frames as MDF4 file with a CAN_DataFrame bus logging channel group
+/// per bus and, if decode is set, a channel group with the signals of each message.
+/// the measurement starts at the UTC time of the server timestamp zero.
+pub fn write_mdf(
+    network: &NetworkRef,
+    frames: &[TraceFrame],
+    decode: bool,
+    epoch: DateTime<Utc>,
+) -> Vec<u8> {
+    let mut writer = MdfWriter::new();
+
+    for bus in network.buses() {
+        let records: Vec<u8> = frames
+            .iter()
+            .filter(|frame| frame.bus == bus.id())
+            .flat_map(can_record)
+            .collect();
+        if records.is_empty() {
+            continue;
+        }
+        let source = writer.bus_source(bus.name());
+        let mut frame_channel =
+            Channel::value("CAN_DataFrame".to_owned(), DT_BYTE_ARRAY, 64, 15 * 8);
+        frame_channel.composition = writer.channels(&can_data_frame_channels());
+        writer.group(
+            "CAN_DataFrame",
+            source,
+            CG_FLAG_BUS_EVENT | CG_FLAG_PLAIN_BUS_EVENT,
+            &[Channel::time(), frame_channel],
+            CAN_RECORD_SIZE,
+            &records,
+        );
+    }
+
+    if decode {
+        let frame_messages: Vec<Option<MessageRef>> = frames
+            .iter()
+            .map(|frame| frame_message(network, frame.bus, frame.id, frame.ide))
+            .collect();
+        for msg in network.messages() {
+            let records: Vec<u8> = frames
+                .iter()
+                .zip(&frame_messages)
+                .filter(|(_, frame_msg)| {
+                    frame_msg
+                        .as_ref()
+                        .is_some_and(|frame_msg| frame_msg.name() == msg.name())
+                })
+                .flat_map(|(frame, _)| {
+                    let mut record = (frame.timestamp as f64 * 1e-6).to_le_bytes().to_vec();
+                    record.extend_from_slice(&frame.data.to_le_bytes());
+                    record
+                })
+                .collect();
+            if records.is_empty() || msg.signals().is_empty() {
+                continue;
+            }
+            let source = writer.bus_source(msg.bus().name());
+            let channels = signal_channels(&mut writer, msg);
+            writer.group(
+                msg.name(),
+                source,
+                0,
+                &channels,
+                MESSAGE_RECORD_SIZE,
+                &records,
+            );
+        }
+    }
+
+    writer.finish(epoch.timestamp_nanos_opt().unwrap_or(0).max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// id, links and data of the block at offset.
+    fn read_block(buf: &[u8], offset: u64) -> (&[u8], Vec<u64>, &[u8]) {
+        let offset = offset as usize;
+        let u64_at = |at: usize| u64::from_le_bytes(buf[at..at + 8].try_into().unwrap());
+        let length = u64_at(offset + 8) as usize;
+        let link_count = u64_at(offset + 16) as usize;
+        let links = (0..link_count)
+            .map(|i| u64_at(offset + 24 + i * 8))
+            .collect();
+        let data = &buf[offset + 24 + link_count * 8..offset + length];
+        (&buf[offset..offset + 4], links, data)
+    }
+
+    fn frame(timestamp: u64, id: u32, ide: bool) -> TraceFrame {
+        TraceFrame {
+            timestamp,
+            bus: 1,
+            id,
+            ide,
+            rtr: false,
+            dlc: 2,
+            data: 0xBEEF,
+        }
+    }
+
+    #[test]
+    fn can_record_layout() {
+        let record = can_record(&frame(1_500_000, 0x123, true));
+        assert_eq!(record.len(), CAN_RECORD_SIZE as usize);
+        assert_eq!(f64::from_le_bytes(record[..8].try_into().unwrap()), 1.5);
+        assert_eq!(record[8], 1);
+        assert_eq!(
+            u32::from_le_bytes(record[9..13].try_into().unwrap()),
+            0x8000_0123
+        );
+        assert_eq!(record[13..15], [2, 2]);
+        assert_eq!(record[15..17], [0xEF, 0xBE]);
+    }
+
+    #[test]
+    fn writes_linked_blocks() {
+        let frames = [frame(0, 0x10, false), frame(1000, 0x11, false)];
+        let records: Vec<u8> = frames.iter().flat_map(can_record).collect();
+        let mut writer = MdfWriter::new();
+        let source = writer.bus_source("can0");
+        let mut channels = vec![Channel::time()];
+        channels.extend(can_data_frame_channels());
+        writer.group(
+            "CAN_DataFrame",
+            source,
+            CG_FLAG_BUS_EVENT,
+            &channels,
+            CAN_RECORD_SIZE,
+            &records,
+        );
+        let buf = writer.finish(42);
+
+        assert_eq!(&buf[..8], b"MDF     ");
+        assert_eq!(&buf[8..12], b"4.10");
+        let (id, links, data) = read_block(&buf, ID_BLOCK_SIZE as u64);
+        assert_eq!(id, b"##HD");
+        assert_eq!(u64::from_le_bytes(data[..8].try_into().unwrap()), 42);
+
+        let (id, dg_links, _) = read_block(&buf, links[0]);
+        assert_eq!(id, b"##DG");
+        assert_eq!(dg_links[0], 0, "a single data group");
+        let (id, cg_links, cg_data) = read_block(&buf, dg_links[1]);
+        assert_eq!(id, b"##CG");
+        assert_eq!(u64::from_le_bytes(cg_data[8..16].try_into().unwrap()), 2);
+        let (id, _, dt_data) = read_block(&buf, dg_links[2]);
+        assert_eq!(id, b"##DT");
+        assert_eq!(dt_data, records.as_slice());
+
+        let mut channel_count = 0;
+        let mut next = cg_links[1];
+        while next != 0 {
+            let (id, cn_links, _) = read_block(&buf, next);
+            assert_eq!(id, b"##CN");
+            channel_count += 1;
+            next = cn_links[0];
+        }
+        assert_eq!(channel_count, channels.len());
+    }
+}
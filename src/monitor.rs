@@ -0,0 +1,505 @@
+use std::{
+    collections::BTreeMap,
+    io::Stdout,
+    time::{Duration, Instant},
+};
+
+use canzero_appdata::AppData;
+use canzero_config::config::{MessageId, MessageRef, NetworkRef, SignalType};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Paragraph, Row, Table, Tabs},
+    Frame, Terminal,
+};
+
+use crate::{
+    config::{frame_message, network_config},
+    decode::{enum_entry_name, signal_enum, signal_raw, signal_value},
+    dump::connect,
+    errors::Result,
+    trace::TraceFrame,
+};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// weight of the latest gap in the averaged message rate.
+const RATE_SMOOTHING: f64 = 0.1;
+
+#[derive(Clone)]
+struct MessageRow {
+    bus: u32,
+    id: MessageId,
+    msg: Option<MessageRef>,
+    count: u64,
+    last: Instant,
+    mean_gap: Option<f64>,
+    dlc: u8,
+    data: u64,
+}
+
+impl MessageRow {
+    fn name(&self) -> &str {
+        self.msg.as_ref().map_or("???", |msg| msg.name())
+    }
+
+    fn values(&self) -> String {
+        let Some(msg) = &self.msg else {
+            return format!("{:016X}", self.data);
+        };
+        msg.signals()
+            .iter()
+            .map(|signal| {
+                let enum_name = signal_enum(msg, signal).and_then(|ty| {
+                    enum_entry_name(&ty, signal_raw(self.data, signal)).map(str::to_owned)
+                });
+                let value = match (enum_name, signal.ty()) {
+                    (Some(name), _) => name,
+                    (None, SignalType::Decimal { .. }) => {
+                        format!("{:.3}", signal_value(self.data, signal))
+                    }
+                    (None, _) => format!("{}", signal_value(self.data, signal)),
+                };
+                format!("{}={value}", signal.name())
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+/// the rows and the view of the monitor, independent of the network config.
+struct MonitorState {
+    /// (id, name) of the buses, one tab per bus.
+    buses: Vec<(u32, String)>,
+    /// rows by (bus, id, ide).
+    rows: BTreeMap<(u32, u32, bool), MessageRow>,
+    /// 0 shows all buses, otherwise the bus at tab - 1.
+    tab: usize,
+    search: String,
+    searching: bool,
+    /// the rows and the time, when the monitor was paused.
+    /// frames are still counted while paused, only the drawn table is frozen.
+    paused: Option<(Instant, BTreeMap<(u32, u32, bool), MessageRow>)>,
+}
+
+impl MonitorState {
+    fn new(buses: Vec<(u32, String)>) -> Self {
+        MonitorState {
+            buses,
+            rows: BTreeMap::new(),
+            tab: 0,
+            search: String::new(),
+            searching: false,
+            paused: None,
+        }
+    }
+
+    /// adds a frame, which was received at now, the message of a new row is resolved by message.
+    fn add(
+        &mut self,
+        frame: &TraceFrame,
+        now: Instant,
+        message: impl FnOnce() -> Option<MessageRef>,
+    ) {
+        match self.rows.get_mut(&(frame.bus, frame.id, frame.ide)) {
+            Some(row) => {
+                let gap = now.duration_since(row.last).as_secs_f64();
+                row.mean_gap = Some(match row.mean_gap {
+                    Some(mean) => mean + RATE_SMOOTHING * (gap - mean),
+                    None => gap,
+                });
+                row.count += 1;
+                row.last = now;
+                row.dlc = frame.dlc;
+                row.data = frame.data;
+            }
+            None => {
+                let id = if frame.ide {
+                    MessageId::ExtendedId(frame.id)
+                } else {
+                    MessageId::StandardId(frame.id)
+                };
+                self.rows.insert(
+                    (frame.bus, frame.id, frame.ide),
+                    MessageRow {
+                        bus: frame.bus,
+                        id,
+                        msg: message(),
+                        count: 1,
+                        last: now,
+                        mean_gap: None,
+                        dlc: frame.dlc,
+                        data: frame.data,
+                    },
+                );
+            }
+        }
+    }
+
+    /// returns false if the monitor should quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.kind != KeyEventKind::Press {
+            return true;
+        }
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+        let tab_count = self.buses.len() + 1;
+        if self.searching {
+            match key.code {
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.searching = false;
+                    self.search.clear();
+                }
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Char(c) => self.search.push(c),
+                _ => (),
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('/') => self.searching = true,
+            KeyCode::Char('p') | KeyCode::Char(' ') => {
+                self.paused = match self.paused {
+                    Some(_) => None,
+                    None => Some((Instant::now(), self.rows.clone())),
+                }
+            }
+            KeyCode::Tab | KeyCode::Right => self.tab = (self.tab + 1) % tab_count,
+            KeyCode::BackTab | KeyCode::Left => self.tab = (self.tab + tab_count - 1) % tab_count,
+            _ => (),
+        }
+        true
+    }
+
+    /// the rows of the selected bus, whose name or id contains the search.
+    fn visible_rows(&self) -> Vec<&MessageRow> {
+        let bus = match self.tab {
+            0 => None,
+            tab => self.buses.get(tab - 1).map(|(id, _)| *id),
+        };
+        let search = self.search.to_lowercase();
+        let rows = match &self.paused {
+            Some((_, rows)) => rows,
+            None => &self.rows,
+        };
+        rows.values()
+            .filter(|row| bus.map_or(true, |bus| row.bus == bus))
+            .filter(|row| {
+                row.name().to_lowercase().contains(&search)
+                    || row.id.to_string().to_lowercase().contains(&search)
+            })
+            .collect()
+    }
+
+    fn bus_name(&self, bus: u32) -> String {
+        self.buses
+            .iter()
+            .find(|(id, _)| *id == bus)
+            .map_or(format!("can{bus}"), |(_, name)| name.clone())
+    }
+}
+
+struct Monitor {
+    network_config: NetworkRef,
+    state: MonitorState,
+}
+
+impl Monitor {
+    fn new(network_config: NetworkRef) -> Self {
+        let buses = network_config
+            .buses()
+            .iter()
+            .map(|bus| (bus.id(), bus.name().to_owned()))
+            .collect();
+        Monitor {
+            network_config,
+            state: MonitorState::new(buses),
+        }
+    }
+
+    fn add(&mut self, frame: &TraceFrame) {
+        let network_config = &self.network_config;
+        self.state.add(frame, Instant::now(), || {
+            frame_message(network_config, frame.bus, frame.id, frame.ide)
+        });
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        self.state.handle_key(key)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let state = &self.state;
+        let mut titles = vec!["all".to_owned()];
+        titles.extend(state.buses.iter().map(|(_, name)| name.clone()));
+        let tabs = Tabs::new(titles)
+            .select(state.tab)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("CANzero monitor"),
+            )
+            .highlight_style(
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(Color::Green),
+            );
+        frame.render_widget(tabs, layout[0]);
+
+        let now = match &state.paused {
+            Some((paused_at, _)) => *paused_at,
+            None => Instant::now(),
+        };
+        let rows: Vec<Row> = state
+            .visible_rows()
+            .into_iter()
+            .map(|row| {
+                let bus = state.bus_name(row.bus);
+                let rate = row
+                    .mean_gap
+                    .filter(|gap| *gap > 0.0)
+                    .map_or("-".to_owned(), |gap| format!("{:.1}Hz", 1.0 / gap));
+                let age = now.duration_since(row.last).as_secs_f64();
+                let style = if row.msg.is_none() {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default()
+                };
+                Row::new(vec![
+                    bus,
+                    format!("{}", row.id),
+                    row.name().to_owned(),
+                    format!("{}", row.dlc),
+                    format!("{}", row.count),
+                    rate,
+                    format!("{age:.1}s"),
+                    row.values(),
+                ])
+                .style(style)
+            })
+            .collect();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(8),
+                Constraint::Length(12),
+                Constraint::Length(28),
+                Constraint::Length(3),
+                Constraint::Length(8),
+                Constraint::Length(9),
+                Constraint::Length(7),
+                Constraint::Min(20),
+            ],
+        )
+        .header(
+            Row::new(vec![
+                "BUS", "ID", "NAME", "DLC", "COUNT", "RATE", "AGE", "VALUES",
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(table, layout[1]);
+
+        let status = if state.searching {
+            format!("search: {}_  (enter to keep, esc to clear)", state.search)
+        } else {
+            let paused = if state.paused.is_some() {
+                "PAUSED  "
+            } else {
+                ""
+            };
+            let search = if state.search.is_empty() {
+                String::new()
+            } else {
+                format!("search: {}  ", state.search)
+            };
+            format!("{paused}{search}q quit  tab switch bus  / search  p pause")
+        };
+        frame.render_widget(Paragraph::new(status), layout[2]);
+    }
+}
+
+/// restores the terminal when the monitor exits, also on errors.
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(std::io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+fn setup_terminal() -> Result<(Terminal<CrosstermBackend<Stdout>>, TerminalGuard)> {
+    enable_raw_mode()?;
+    let guard = TerminalGuard;
+    execute!(std::io::stdout(), EnterAlternateScreen)?;
+    let terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    Ok((terminal, guard))
+}
+
+pub async fn command_monitor() -> Result<()> {
+    let appdata = AppData::read()?;
+    let network_config = network_config(&appdata)?;
+    let (_, tcpcan) = connect().await?;
+
+    let mut monitor = Monitor::new(network_config);
+
+    // crossterm events are read blocking on their own thread.
+    let (key_tx, mut key_rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || loop {
+        match event::read() {
+            Ok(Event::Key(key)) => {
+                if key_tx.send(key).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => (),
+            Err(_) => break,
+        }
+    });
+
+    let (mut terminal, _guard) = setup_terminal()?;
+    let mut redraw = tokio::time::interval(REDRAW_INTERVAL);
+    loop {
+        tokio::select! {
+            frame = tcpcan.recv() => {
+                let Some(frame) = frame else {
+                    break;
+                };
+                monitor.add(&TraceFrame::from_network_frame(&frame));
+            }
+            key = key_rx.recv() => {
+                let Some(key) = key else {
+                    break;
+                };
+                if !monitor.handle_key(key) {
+                    break;
+                }
+            }
+            _ = redraw.tick() => {
+                terminal.draw(|frame| monitor.draw(frame))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(bus: u32, id: u32) -> TraceFrame {
+        TraceFrame {
+            timestamp: 0,
+            bus,
+            id,
+            ide: false,
+            rtr: false,
+            dlc: 1,
+            data: 0,
+        }
+    }
+
+    fn press(state: &mut MonitorState, code: KeyCode) -> bool {
+        state.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn visible_ids(state: &MonitorState) -> Vec<(u32, u32)> {
+        state
+            .visible_rows()
+            .iter()
+            .map(|row| (row.bus, row.id.as_u32()))
+            .collect()
+    }
+
+    fn sample_state() -> MonitorState {
+        let mut state = MonitorState::new(vec![(0, "can0".to_owned()), (1, "can1".to_owned())]);
+        let now = Instant::now();
+        for (bus, id) in [(0, 0x10), (0, 0x123), (1, 0x124)] {
+            state.add(&frame(bus, id), now, || None);
+        }
+        state
+    }
+
+    #[test]
+    fn counts_frames_and_averages_the_rate() {
+        let mut state = MonitorState::new(vec![]);
+        let start = Instant::now();
+        for i in 0..3 {
+            state.add(
+                &frame(0, 0x10),
+                start + Duration::from_millis(i * 100),
+                || None,
+            );
+        }
+        let row = &state.rows[&(0, 0x10, false)];
+        assert_eq!(row.count, 3);
+        assert!((row.mean_gap.unwrap() - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tabs_show_one_bus() {
+        let mut state = sample_state();
+        assert_eq!(state.visible_rows().len(), 3);
+        press(&mut state, KeyCode::Tab);
+        assert_eq!(visible_ids(&state), [(0, 0x10), (0, 0x123)]);
+        press(&mut state, KeyCode::Tab);
+        assert_eq!(visible_ids(&state), [(1, 0x124)]);
+        // wraps around to all buses in both directions.
+        press(&mut state, KeyCode::Tab);
+        assert_eq!(state.tab, 0);
+        press(&mut state, KeyCode::BackTab);
+        assert_eq!(state.tab, 2);
+    }
+
+    #[test]
+    fn search_filters_rows() {
+        let mut state = sample_state();
+        press(&mut state, KeyCode::Char('/'));
+        for c in MessageId::StandardId(0x123).to_string().chars() {
+            // keys are typed into the search instead of switching tabs or quitting.
+            assert!(press(&mut state, KeyCode::Char(c)));
+        }
+        press(&mut state, KeyCode::Enter);
+        assert!(!state.searching);
+        assert_eq!(visible_ids(&state), [(0, 0x123)]);
+        press(&mut state, KeyCode::Char('/'));
+        press(&mut state, KeyCode::Esc);
+        assert!(state.search.is_empty());
+        assert_eq!(state.visible_rows().len(), 3);
+        assert!(!press(&mut state, KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn pause_freezes_the_table() {
+        let mut state = sample_state();
+        press(&mut state, KeyCode::Char('p'));
+        state.add(&frame(0, 0x10), Instant::now(), || None);
+        state.add(&frame(1, 0x200), Instant::now(), || None);
+        assert_eq!(state.visible_rows().len(), 3);
+        assert_eq!(state.visible_rows()[0].count, 1);
+        // frames are still counted while paused.
+        press(&mut state, KeyCode::Char('p'));
+        assert_eq!(state.visible_rows().len(), 4);
+        assert_eq!(state.visible_rows()[0].count, 2);
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    errors::{Error, Result},
+    trace::{interface_bus, Trace, TraceFrame},
+};
+
+pub const PCAPNG_MAGIC: u32 = SECTION_HEADER_BLOCK;
+pub const PCAP_MAGIC: u32 = 0xA1B2C3D4;
+pub const PCAP_MAGIC_NANOS: u32 = 0xA1B23C4D;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+const LINKTYPE_CAN_SOCKETCAN: u16 = 227;
+
+const OPT_END_OF_OPT: u16 = 0;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_IF_TSOFFSET: u16 = 14;
+
+const CAN_EFF_FLAG: u32 = 0x80000000;
+const CAN_RTR_FLAG: u32 = 0x40000000;
+const CAN_ERR_FLAG: u32 = 0x20000000;
+/// header (id, length, padding) and data of a SocketCAN frame.
+const SOCKETCAN_FRAME_SIZE: usize = 16;
+
+fn option(out: &mut Vec<u8>, code: u16, value: &[u8]) {
+    out.extend_from_slice(&code.to_le_bytes());
+    out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    out.extend_from_slice(value);
+    while out.len() % 4 != 0 {
+        out.push(0);
+    }
+}
+
+fn encode_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let length = (12 + body.len()) as u32;
+    let mut block = Vec::with_capacity(length as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&length.to_le_bytes());
+    block.extend_from_slice(body);
+    block.extend_from_slice(&length.to_le_bytes());
+    block
+}
+
+/// the frame as struct can_frame with the id in network byte order.
+fn socketcan_frame(frame: &TraceFrame) -> [u8; SOCKETCAN_FRAME_SIZE] {
+    let mut id = frame.id;
+    if frame.ide {
+        id |= CAN_EFF_FLAG;
+    }
+    if frame.rtr {
+        id |= CAN_RTR_FLAG;
+    }
+    let mut packet = [0; SOCKETCAN_FRAME_SIZE];
+    packet[..4].copy_from_slice(&id.to_be_bytes());
+    packet[4] = frame.dlc.min(8);
+    packet[8..].copy_from_slice(&frame.data.to_le_bytes());
+    packet
+}
+
+/// writes frames as pcapng with one SocketCAN interface per bus.
+/// with an epoch, its seconds are the if_tsoffset of the interfaces, such that the frames
+/// are dated in UTC, otherwise timestamps are microseconds relative to the timebase of the server.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    /// bus ids of the interfaces, in order of their interface description blocks.
+    interfaces: Vec<u32>,
+    tsoffset: Option<i64>,
+    /// microseconds of the epoch after tsoffset, added to the timestamps.
+    offset: u64,
+}
+
+impl PcapWriter<BufWriter<File>> {
+    pub fn create(
+        path: &Path,
+        buses: &[(u32, String)],
+        epoch: Option<DateTime<Utc>>,
+    ) -> Result<Self> {
+        PcapWriter::new(BufWriter::new(File::create(path)?), buses, epoch)
+    }
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(writer: W, buses: &[(u32, String)], epoch: Option<DateTime<Utc>>) -> Result<Self> {
+        let mut pcap = PcapWriter {
+            writer,
+            interfaces: vec![],
+            tsoffset: epoch.map(|epoch| epoch.timestamp()),
+            offset: epoch.map_or(0, |epoch| epoch.timestamp_subsec_micros() as u64),
+        };
+        let mut body = vec![];
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // unknown section length
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        option(
+            &mut body,
+            OPT_SHB_USERAPPL,
+            format!("canzero-cli {}", env!("CARGO_PKG_VERSION")).as_bytes(),
+        );
+        option(&mut body, OPT_END_OF_OPT, &[]);
+        pcap.writer
+            .write_all(&encode_block(SECTION_HEADER_BLOCK, &body))?;
+        for (bus_id, bus_name) in buses {
+            pcap.add_interface(*bus_id, bus_name)?;
+        }
+        Ok(pcap)
+    }
+
+    fn add_interface(&mut self, bus_id: u32, bus_name: &str) -> Result<u32> {
+        let mut body = vec![];
+        body.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // no snapshot length limit
+        body.extend_from_slice(&0u32.to_le_bytes());
+        option(&mut body, OPT_IF_NAME, bus_name.as_bytes());
+        // microseconds
+        option(&mut body, OPT_IF_TSRESOL, &[6]);
+        if let Some(tsoffset) = self.tsoffset {
+            option(&mut body, OPT_IF_TSOFFSET, &tsoffset.to_le_bytes());
+        }
+        option(&mut body, OPT_END_OF_OPT, &[]);
+        self.writer
+            .write_all(&encode_block(INTERFACE_DESCRIPTION_BLOCK, &body))?;
+        self.interfaces.push(bus_id);
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+
+    pub fn write(&mut self, frame: &TraceFrame) -> Result<()> {
+        // frames of unknown buses get their own interface.
+        let interface = match self.interfaces.iter().position(|id| *id == frame.bus) {
+            Some(interface) => interface as u32,
+            None => self.add_interface(frame.bus, &format!("can{}", frame.bus))?,
+        };
+        let timestamp = frame.timestamp + self.offset;
+        let mut body = vec![];
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&(SOCKETCAN_FRAME_SIZE as u32).to_le_bytes());
+        body.extend_from_slice(&(SOCKETCAN_FRAME_SIZE as u32).to_le_bytes());
+        body.extend_from_slice(&socketcan_frame(frame));
+        self.writer
+            .write_all(&encode_block(ENHANCED_PACKET_BLOCK, &body))?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn u16_at(content: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        content.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(content: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        content.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn parse_socketcan_frame(timestamp: u64, bus: u32, packet: &[u8]) -> Option<TraceFrame> {
+    let id = u32::from_be_bytes(packet.get(..4)?.try_into().ok()?);
+    let dlc = *packet.get(4)?;
+    let mut data = [0; 8];
+    let payload = packet.get(8..)?;
+    let len = payload.len().min(8).min(dlc as usize);
+    data[..len].copy_from_slice(&payload[..len]);
+    Some(TraceFrame {
+        timestamp,
+        bus,
+        id: id & !(CAN_EFF_FLAG | CAN_RTR_FLAG | CAN_ERR_FLAG),
+        ide: id & CAN_EFF_FLAG != 0,
+        rtr: id & CAN_RTR_FLAG != 0,
+        dlc,
+        data: u64::from_le_bytes(data),
+    })
+}
+
+/// timestamp in microseconds for a if_tsresol option.
+fn timestamp_micros(timestamp: u64, resolution: u8) -> u64 {
+    let per_second: f64 = if resolution & 0x80 != 0 {
+        2f64.powi((resolution & 0x7F) as i32)
+    } else {
+        10f64.powi(resolution as i32)
+    };
+    (timestamp as f64 * 1e6 / per_second).round() as u64
+}
+
+fn invalid_capture() -> Error {
+    Error::InvalidTraceFile("pcap capture".to_owned())
+}
+
+/// type, length and body of the block at offset.
+fn block(content: &[u8], offset: usize) -> Option<(u32, usize, &[u8])> {
+    let block_type = u32_at(content, offset)?;
+    let length = u32_at(content, offset + 4)? as usize;
+    let body = content.get(offset + 8..offset + length.checked_sub(4)?)?;
+    Some((block_type, length, body))
+}
+
+struct Interface {
+    link_type: u16,
+    name: Option<String>,
+    resolution: u8,
+    /// seconds added to the timestamps of the interface.
+    tsoffset: i64,
+}
+
+fn parse_interface(body: &[u8]) -> Option<Interface> {
+    let mut interface = Interface {
+        link_type: u16_at(body, 0)?,
+        name: None,
+        resolution: 6,
+        tsoffset: 0,
+    };
+    let mut option_offset = 8;
+    while option_offset + 4 <= body.len() {
+        let code = u16_at(body, option_offset)?;
+        let option_length = u16_at(body, option_offset + 2)? as usize;
+        let value = body.get(option_offset + 4..option_offset + 4 + option_length)?;
+        match code {
+            OPT_END_OF_OPT => break,
+            OPT_IF_NAME => interface.name = Some(String::from_utf8_lossy(value).into_owned()),
+            OPT_IF_TSRESOL => interface.resolution = *value.first()?,
+            OPT_IF_TSOFFSET => {
+                interface.tsoffset = i64::from_le_bytes(value.get(..8)?.try_into().ok()?)
+            }
+            _ => (),
+        }
+        option_offset += 4 + option_length.div_ceil(4) * 4;
+    }
+    Some(interface)
+}
+
+/// interface, timestamp and packet of an enhanced packet block.
+fn parse_packet(body: &[u8]) -> Option<(usize, u64, &[u8])> {
+    let interface = u32_at(body, 0)? as usize;
+    let timestamp = ((u32_at(body, 4)? as u64) << 32) | u32_at(body, 8)? as u64;
+    let captured = u32_at(body, 12)? as usize;
+    Some((interface, timestamp, body.get(20..20 + captured)?))
+}
+
+/// the frames with unix timestamps.
+fn parse_pcapng(content: &[u8], buses: &[(u32, String)]) -> Result<Vec<TraceFrame>> {
+    let mut frames = vec![];
+    // interfaces with the bus of SocketCAN interfaces.
+    let mut interfaces: Vec<(Interface, Option<u32>)> = vec![];
+    let mut offset = 0;
+    while offset < content.len() {
+        let (block_type, length, body) = block(content, offset).ok_or_else(invalid_capture)?;
+        match block_type {
+            SECTION_HEADER_BLOCK => {
+                if u32_at(body, 0) != Some(BYTE_ORDER_MAGIC) {
+                    // big endian sections are not supported
+                    return Err(invalid_capture());
+                }
+                interfaces.clear();
+            }
+            INTERFACE_DESCRIPTION_BLOCK => {
+                let interface = parse_interface(body).ok_or_else(invalid_capture)?;
+                let bus = match (&interface.name, interface.link_type) {
+                    (Some(name), LINKTYPE_CAN_SOCKETCAN) => Some(interface_bus(buses, name)?),
+                    (None, LINKTYPE_CAN_SOCKETCAN) => Some(interfaces.len() as u32),
+                    _ => None,
+                };
+                interfaces.push((interface, bus));
+            }
+            ENHANCED_PACKET_BLOCK => {
+                let (index, timestamp, packet) = parse_packet(body).ok_or_else(invalid_capture)?;
+                let (interface, bus) = interfaces.get(index).ok_or_else(invalid_capture)?;
+                if let Some(bus) = bus {
+                    let timestamp = timestamp_micros(timestamp, interface.resolution) as i64
+                        + interface.tsoffset * 1_000_000;
+                    let frame = parse_socketcan_frame(timestamp.max(0) as u64, *bus, packet);
+                    frames.push(frame.ok_or_else(invalid_capture)?);
+                }
+            }
+            _ => (),
+        }
+        offset += length;
+    }
+    Ok(frames)
+}
+
+fn parse_pcap(content: &[u8]) -> Option<Vec<TraceFrame>> {
+    let nanos = u32_at(content, 0)? == PCAP_MAGIC_NANOS;
+    if u32_at(content, 20)? as u16 != LINKTYPE_CAN_SOCKETCAN {
+        return None;
+    }
+    let mut frames = vec![];
+    let mut offset = 24;
+    while offset < content.len() {
+        let secs = u32_at(content, offset)? as u64;
+        let fraction = u32_at(content, offset + 4)? as u64;
+        let captured = u32_at(content, offset + 8)? as usize;
+        let packet = content.get(offset + 16..offset + 16 + captured)?;
+        let micros = if nanos { fraction / 1000 } else { fraction };
+        // classic pcap files have no interfaces, all frames are on bus 0.
+        frames.push(parse_socketcan_frame(secs * 1_000_000 + micros, 0, packet)?);
+        offset += 16 + captured;
+    }
+    Some(frames)
+}
+
+/// reads the SocketCAN frames of a pcapng or classic pcap file (little endian),
+/// interfaces are mapped to buses by their name.
+pub fn read_pcap(content: &[u8], buses: &[(u32, String)]) -> Result<Trace> {
+    let frames = match u32_at(content, 0) {
+        Some(PCAPNG_MAGIC) => parse_pcapng(content, buses)?,
+        Some(PCAP_MAGIC | PCAP_MAGIC_NANOS) => parse_pcap(content).ok_or_else(invalid_capture)?,
+        _ => return Err(invalid_capture()),
+    };
+    Ok(Trace::from_unix(frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::tests::{sample_buses, sample_frames, sample_trace, utc_frames};
+
+    fn write_pcapng(trace: &Trace) -> Vec<u8> {
+        let mut pcap = PcapWriter::new(vec![], &sample_buses(), trace.epoch).unwrap();
+        for frame in &trace.frames {
+            pcap.write(frame).unwrap();
+        }
+        pcap.writer
+    }
+
+    #[test]
+    fn pcapng_round_trip() {
+        let content = write_pcapng(&sample_trace());
+        assert_eq!(u32_at(&content, 0), Some(PCAPNG_MAGIC));
+        let read = read_pcap(&content, &sample_buses()).unwrap();
+        assert_eq!(utc_frames(&read), utc_frames(&sample_trace()));
+    }
+
+    #[test]
+    fn pcapng_without_epoch() {
+        let trace = Trace {
+            epoch: None,
+            frames: sample_frames(),
+        };
+        let read = read_pcap(&write_pcapng(&trace), &sample_buses()).unwrap();
+        assert_eq!(read.epoch, DateTime::from_timestamp_micros(12));
+        assert_eq!(utc_frames(&read), utc_frames(&trace));
+    }
+
+    #[test]
+    fn reads_classic_pcap() {
+        let mut content = vec![];
+        content.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+        content.extend_from_slice(&2u16.to_le_bytes());
+        content.extend_from_slice(&4u16.to_le_bytes());
+        content.extend_from_slice(&[0; 8]); // time zone and accuracy
+        content.extend_from_slice(&65535u32.to_le_bytes());
+        content.extend_from_slice(&(LINKTYPE_CAN_SOCKETCAN as u32).to_le_bytes());
+        let frame = &sample_frames()[3];
+        content.extend_from_slice(&5000u32.to_le_bytes());
+        content.extend_from_slice(&123u32.to_le_bytes());
+        content.extend_from_slice(&(SOCKETCAN_FRAME_SIZE as u32).to_le_bytes());
+        content.extend_from_slice(&(SOCKETCAN_FRAME_SIZE as u32).to_le_bytes());
+        content.extend_from_slice(&socketcan_frame(frame));
+
+        let read = read_pcap(&content, &sample_buses()).unwrap();
+        let trace = Trace {
+            epoch: None,
+            frames: vec![TraceFrame {
+                bus: 0,
+                ..frame.clone()
+            }],
+        };
+        assert_eq!(utc_frames(&read), utc_frames(&trace));
+    }
+
+    #[test]
+    fn rejects_truncated_captures() {
+        let content = write_pcapng(&sample_trace());
+        let truncated = &content[..content.len() - 4];
+        assert!(read_pcap(truncated, &sample_buses()).is_err());
+        assert!(read_pcap(b"nope", &sample_buses()).is_err());
+    }
+}
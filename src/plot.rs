@@ -0,0 +1,195 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use canzero_appdata::AppData;
+use plotters::{coord::Shift, prelude::*};
+
+use crate::{
+    config::network_config,
+    errors::{Error, Result},
+};
+
+const SUBPLOT_WIDTH: u32 = 1200;
+const SUBPLOT_HEIGHT: u32 = 300;
+
+/// one column of a logged object entry.
+struct Series {
+    name: String,
+    unit: Option<String>,
+    points: Vec<(f64, f64)>,
+}
+
+fn plot_error<E: std::fmt::Debug>(err: E) -> Error {
+    Error::PlotFailed(format!("{err:?}"))
+}
+
+/// reads a csv file in the layout of example-log, e.g.
+/// timestamp; foo.x; foo.y
+/// and returns the column names without the timestamp and
+/// the rows with the timestamp in seconds.
+fn read_csv(path: &Path) -> Result<(Vec<String>, Vec<Vec<f64>>)> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Err(Error::FileNotFound(format!("{path:?}")));
+    };
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return Err(Error::InvalidLogFile(format!("{path:?} is empty")));
+    };
+    let columns: Vec<String> = header
+        .split(';')
+        .skip(1)
+        .map(|column| column.trim().to_owned())
+        .collect();
+    let mut rows = vec![];
+    for (line_number, line) in lines.enumerate() {
+        let row = line
+            .split(';')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<std::result::Result<Vec<f64>, _>>();
+        match row {
+            Ok(mut row) if row.len() == columns.len() + 1 => {
+                // timestamps are logged in microseconds.
+                row[0] *= 1e-6;
+                rows.push(row);
+            }
+            _ => {
+                return Err(Error::InvalidLogFile(format!(
+                    "{path:?} line {}",
+                    line_number + 2
+                )))
+            }
+        }
+    }
+    Ok((columns, rows))
+}
+
+fn value_range(points: &[(f64, f64)]) -> Range<f64> {
+    let min = points.iter().map(|(_, y)| *y).fold(f64::INFINITY, f64::min);
+    let max = points
+        .iter()
+        .map(|(_, y)| *y)
+        .fold(f64::NEG_INFINITY, f64::max);
+    if !min.is_finite() || !max.is_finite() {
+        return 0.0..1.0;
+    }
+    let padding = if max > min { (max - min) * 0.05 } else { 1.0 };
+    (min - padding)..(max + padding)
+}
+
+fn draw<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    series: &[Series],
+    time: Range<f64>,
+) -> Result<()> {
+    root.fill(&WHITE).map_err(plot_error)?;
+    let areas = root.split_evenly((series.len(), 1));
+    for (area, series) in areas.iter().zip(series) {
+        let mut chart = ChartBuilder::on(area)
+            .caption(&series.name, ("sans-serif", 20))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(70)
+            .build_cartesian_2d(time.clone(), value_range(&series.points))
+            .map_err(plot_error)?;
+        let y_desc = match &series.unit {
+            Some(unit) => format!("{} [{unit}]", series.name),
+            None => series.name.clone(),
+        };
+        chart
+            .configure_mesh()
+            .x_desc("time [s]")
+            .y_desc(y_desc)
+            .draw()
+            .map_err(plot_error)?;
+        chart
+            .draw_series(LineSeries::new(series.points.iter().copied(), &BLUE))
+            .map_err(plot_error)?;
+    }
+    root.present().map_err(plot_error)?;
+    Ok(())
+}
+
+pub fn command_plot(
+    log_dir: PathBuf,
+    node_name: String,
+    entries: Vec<String>,
+    output: PathBuf,
+) -> Result<()> {
+    // units are taken from the network config, plotting works without one.
+    let node = AppData::read()
+        .ok()
+        .and_then(|appdata| network_config(&appdata).ok())
+        .and_then(|network| {
+            network
+                .nodes()
+                .iter()
+                .find(|node| node.name() == node_name)
+                .cloned()
+        });
+
+    let mut series = vec![];
+    for entry in &entries {
+        let (columns, rows) = read_csv(&log_dir.join(&node_name).join(format!("{entry}.csv")))?;
+        let unit = node.as_ref().and_then(|node| {
+            node.object_entries()
+                .iter()
+                .find(|oe| oe.name() == entry)
+                .and_then(|oe| oe.unit().map(|unit| unit.to_string()))
+        });
+        for (i, column) in columns.iter().enumerate() {
+            let name = if columns.len() == 1 {
+                format!("{node_name}.{entry}")
+            } else {
+                format!("{node_name}.{entry}.{column}")
+            };
+            series.push(Series {
+                name,
+                unit: unit.clone(),
+                points: rows.iter().map(|row| (row[0], row[i + 1])).collect(),
+            });
+        }
+    }
+    if series.is_empty() {
+        return Err(Error::InvalidLogFile(format!(
+            "no values logged for {entries:?}"
+        )));
+    }
+
+    // all subplots share the same time axis.
+    let start = series
+        .iter()
+        .flat_map(|series| series.points.first())
+        .map(|(t, _)| *t)
+        .fold(f64::INFINITY, f64::min);
+    let end = series
+        .iter()
+        .flat_map(|series| series.points.last())
+        .map(|(t, _)| *t)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let time = if start < end {
+        start..end
+    } else if start.is_finite() {
+        start..(start + 1.0)
+    } else {
+        0.0..1.0
+    };
+
+    let size = (SUBPLOT_WIDTH, SUBPLOT_HEIGHT * series.len() as u32);
+    match output.extension().and_then(|ext| ext.to_str()) {
+        Some("svg") => draw(
+            SVGBackend::new(&output, size).into_drawing_area(),
+            &series,
+            time,
+        )?,
+        Some("png") => draw(
+            BitMapBackend::new(&output, size).into_drawing_area(),
+            &series,
+            time,
+        )?,
+        _ => return Err(Error::UnsupportedFileFormat(format!("{output:?}"))),
+    }
+    println!("Plotted {} series to {output:?}", series.len());
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use canzero_appdata::AppData;
+use color_print::cprintln;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{config_path, config_source},
+    config_repo::{check_out, restore, ConfigSource},
+    errors::{Error, Result},
+};
+
+const PROFILES_FILE: &'static str = "profiles.yaml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub path: PathBuf,
+    /// set if the config was selected from a git repository.
+    pub source: Option<ConfigSource>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    fn path() -> PathBuf {
+        let mut path = AppData::dir();
+        path.push(PROFILES_FILE);
+        path
+    }
+
+    pub fn read() -> Result<Profiles> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(Profiles::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        serde_yaml::from_str(&content).map_err(|err| Error::InvalidConfig(format!("{err:?}")))
+    }
+
+    fn write(&self) -> Result<()> {
+        let content =
+            serde_yaml::to_string(self).map_err(|err| Error::InvalidConfig(format!("{err:?}")))?;
+        std::fs::write(Self::path(), content)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+}
+
+/// resolves a --config argument, which is either the name of a profile or a path.
+/// the pinned commit of a profile from a git repository is checked out.
+pub fn resolve_profile_or_path(name_or_path: &str) -> Result<Profile> {
+    let profiles = Profiles::read()?;
+    if let Some(profile) = profiles.get(name_or_path) {
+        return match &profile.source {
+            Some(source) => {
+                check_out(source)?;
+                Ok(Profile {
+                    path: source.config_path(),
+                    source: Some(source.clone()),
+                })
+            }
+            None => Ok(profile.clone()),
+        };
+    }
+    let path = PathBuf::from(name_or_path);
+    if path.exists() {
+        Ok(Profile { path, source: None })
+    } else {
+        Err(Error::InvalidProfileName(name_or_path.to_owned()))
+    }
+}
+
+pub fn command_config_add(name: String, path: Option<PathBuf>) -> Result<()> {
+    let mut profiles = Profiles::read()?;
+    let profile = match path {
+        Some(path) => Profile {
+            path: path.canonicalize()?,
+            source: None,
+        },
+        None => {
+            // store the current selection, which might be given by --config.
+            let appdata = AppData::read()?;
+            let Some(path) = config_path(&appdata) else {
+                return Err(Error::NoConfigSelected);
+            };
+            Profile {
+                path,
+                source: config_source()?,
+            }
+        }
+    };
+    println!("Added profile {name} = {:?}", profile.path);
+    profiles.profiles.insert(name, profile);
+    profiles.write()
+}
+
+pub fn command_config_remove(name: String) -> Result<()> {
+    let mut profiles = Profiles::read()?;
+    if profiles.profiles.remove(&name).is_none() {
+        return Err(Error::InvalidProfileName(name));
+    }
+    profiles.write()
+}
+
+pub fn command_config_use(name: String) -> Result<()> {
+    let profiles = Profiles::read()?;
+    let Some(profile) = profiles.get(&name) else {
+        return Err(Error::InvalidProfileName(name));
+    };
+    match &profile.source {
+        Some(source) => {
+            // the checkout might have been changed by another profile.
+            restore(source)?;
+            cprintln!(
+                "<green>Using profile {name} ({} at {})</green>",
+                source.url,
+                source.commit
+            );
+        }
+        None => {
+            let mut appdata = AppData::read()?;
+            appdata.set_config_path(Some(profile.path.clone()))?;
+            ConfigSource::clear()?;
+            cprintln!("<green>Using profile {name} ({:?})</green>", profile.path);
+        }
+    }
+    Ok(())
+}
+
+pub fn command_config_list() -> Result<()> {
+    let profiles = Profiles::read()?;
+    let appdata = AppData::read()?;
+    let current = appdata.get_config_path().map(|p| p.to_path_buf());
+    if profiles.profiles.is_empty() {
+        println!("No profiles, add one with \"config add <name> <path>\"");
+    }
+    for (name, profile) in &profiles.profiles {
+        let path = match &profile.source {
+            Some(source) => source.config_path(),
+            None => profile.path.clone(),
+        };
+        let active = current.as_ref() == Some(&path);
+        match (&profile.source, active) {
+            (Some(source), true) => cprintln!(
+                "<green>* {name:15} {:?} ({} at {})</green>",
+                profile.path,
+                source.url,
+                source.rev
+            ),
+            (Some(source), false) => println!(
+                "  {name:15} {:?} ({} at {})",
+                profile.path, source.url, source.rev
+            ),
+            (None, true) => cprintln!("<green>* {name:15} {:?}</green>", profile.path),
+            (None, false) => println!("  {name:15} {:?}", profile.path),
+        }
+    }
+    Ok(())
+}
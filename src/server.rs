@@ -2,12 +2,12 @@
 use canzero_appdata::AppData;
 use canzero_server::Server;
 
-use crate::errors::Result;
+use crate::{config::network_config, errors::Result};
 
 pub async fn command_server() -> Result<()> {
 
     let appdata = AppData::read()?;
-    let network_config = appdata.config()?;
+    let network_config = network_config(&appdata)?;
     let server = Server::create(network_config).await?;
 
     server.start();
@@ -0,0 +1,279 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use canzero_appdata::AppData;
+use canzero_config::config::{MessageId, NetworkRef};
+use color_print::cprintln;
+
+use crate::{
+    busload::{frame_bits, message_period, print_load},
+    config::network_config,
+    dump::connect,
+    errors::Result,
+    trace::{bus_names, read_trace, TraceFrame},
+};
+
+/// gaps longer than this factor of the configured interval count as missed cycles.
+const MISSED_CYCLE_FACTOR: f64 = 1.5;
+
+#[derive(Default)]
+struct MessageStats {
+    count: u64,
+    last: Option<u64>,
+    gap_sum: f64,
+    gap_square_sum: f64,
+    min_gap: Option<f64>,
+    max_gap: Option<f64>,
+    missed: u64,
+}
+
+impl MessageStats {
+    fn add(&mut self, timestamp: u64, interval: Option<Duration>) {
+        self.count += 1;
+        if let Some(last) = self.last {
+            let gap = timestamp.saturating_sub(last) as f64 * 1e-6;
+            self.gap_sum += gap;
+            self.gap_square_sum += gap * gap;
+            self.min_gap = Some(self.min_gap.map_or(gap, |min| min.min(gap)));
+            self.max_gap = Some(self.max_gap.map_or(gap, |max| max.max(gap)));
+            if let Some(interval) = interval.filter(|interval| !interval.is_zero()) {
+                let cycles = gap / interval.as_secs_f64();
+                if cycles > MISSED_CYCLE_FACTOR {
+                    self.missed += cycles.round() as u64 - 1;
+                }
+            }
+        }
+        self.last = Some(timestamp);
+    }
+
+    /// mean and standard deviation of the gaps between two frames.
+    fn period(&self) -> Option<(f64, f64)> {
+        if self.count < 2 {
+            return None;
+        }
+        let n = (self.count - 1) as f64;
+        let mean = self.gap_sum / n;
+        let variance = (self.gap_square_sum / n - mean * mean).max(0.0);
+        Some((mean, variance.sqrt()))
+    }
+}
+
+struct Stats {
+    network_config: NetworkRef,
+    /// message index by (bus, id, ide).
+    index: HashMap<(u32, u32, bool), usize>,
+    messages: Vec<MessageStats>,
+    /// frame count of unknown ids by (bus, id, ide), including known ids on another bus.
+    unknown: BTreeMap<(u32, u32, bool), u64>,
+    /// transmitted bits by bus id.
+    bus_bits: BTreeMap<u32, u64>,
+    frames: u64,
+    first: Option<u64>,
+    last: Option<u64>,
+}
+
+fn ms(seconds: f64) -> String {
+    format!("{:8.2}ms", seconds * 1000.0)
+}
+
+impl Stats {
+    fn new(network_config: NetworkRef) -> Self {
+        let index = network_config
+            .messages()
+            .iter()
+            .enumerate()
+            .map(|(i, msg)| ((msg.bus().id(), msg.id().as_u32(), msg.id().ide()), i))
+            .collect();
+        let messages = network_config
+            .messages()
+            .iter()
+            .map(|_| MessageStats::default())
+            .collect();
+        Stats {
+            network_config,
+            index,
+            messages,
+            unknown: BTreeMap::new(),
+            bus_bits: BTreeMap::new(),
+            frames: 0,
+            first: None,
+            last: None,
+        }
+    }
+
+    fn add(&mut self, frame: &TraceFrame) {
+        self.frames += 1;
+        self.first = Some(self.first.unwrap_or(frame.timestamp).min(frame.timestamp));
+        self.last = Some(self.last.unwrap_or(frame.timestamp).max(frame.timestamp));
+        *self.bus_bits.entry(frame.bus).or_default() += frame_bits(frame.dlc, frame.ide) as u64;
+        match self.index.get(&(frame.bus, frame.id, frame.ide)) {
+            Some(i) => {
+                let interval = message_period(&self.network_config.messages()[*i]);
+                self.messages[*i].add(frame.timestamp, interval);
+            }
+            None => {
+                *self
+                    .unknown
+                    .entry((frame.bus, frame.id, frame.ide))
+                    .or_default() += 1
+            }
+        }
+    }
+
+    fn bus_name(&self, bus_id: u32) -> String {
+        self.network_config
+            .buses()
+            .iter()
+            .find(|bus| bus.id() == bus_id)
+            .map_or(format!("can{bus_id}"), |bus| bus.name().to_owned())
+    }
+
+    fn print(&self) {
+        let duration = match (self.first, self.last) {
+            (Some(first), Some(last)) => (last - first) as f64 * 1e-6,
+            _ => 0.0,
+        };
+        println!("{} frames in {duration:.3}s", self.frames);
+
+        println!(
+            "\n{:25} {:6} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>8}",
+            "MESSAGE", "BUS", "COUNT", "PERIOD", "JITTER", "MIN GAP", "MAX GAP", "CYCLE", "MISSED"
+        );
+        for (msg, stats) in self.network_config.messages().iter().zip(&self.messages) {
+            if stats.count == 0 {
+                continue;
+            }
+            let (period, jitter) = match stats.period() {
+                Some((period, jitter)) => (ms(period), ms(jitter)),
+                None => ("-".to_owned(), "-".to_owned()),
+            };
+            let min_gap = stats.min_gap.map_or("-".to_owned(), ms);
+            let max_gap = stats.max_gap.map_or("-".to_owned(), ms);
+            let interval = message_period(msg);
+            let cycle = interval.map_or("-".to_owned(), |interval| ms(interval.as_secs_f64()));
+            let line = format!(
+                "{:25} {:6} {:8} {period:>10} {jitter:>10} {min_gap:>10} {max_gap:>10} {cycle:>10}",
+                msg.name(),
+                msg.bus().name(),
+                stats.count,
+            );
+            if stats.missed > 0 {
+                cprintln!("{line} <red>{:8}</red>", stats.missed);
+            } else {
+                println!("{line} {:8}", stats.missed);
+            }
+        }
+        let silent: Vec<&str> = self
+            .network_config
+            .messages()
+            .iter()
+            .zip(&self.messages)
+            .filter(|(msg, stats)| stats.count == 0 && message_period(msg).is_some())
+            .map(|(msg, _)| msg.name())
+            .collect();
+        if !silent.is_empty() {
+            cprintln!("<yellow>Never received : {}</yellow>", silent.join(", "));
+        }
+
+        println!("\nBUS LOAD");
+        if duration > 0.0 {
+            for bus in self.network_config.buses() {
+                let bits = self.bus_bits.get(&bus.id()).copied().unwrap_or(0);
+                let load = bits as f64 / (duration * bus.baudrate() as f64);
+                print_load(&format!("  {:25}", bus.name()), load);
+            }
+        }
+
+        if !self.unknown.is_empty() {
+            println!("\nUNKNOWN IDS");
+            for ((bus, id, ide), count) in &self.unknown {
+                let id = if *ide {
+                    MessageId::ExtendedId(*id)
+                } else {
+                    MessageId::StandardId(*id)
+                };
+                cprintln!(
+                    "  <red>{:6} {id:5}</red> : {count} frames",
+                    self.bus_name(*bus)
+                );
+            }
+        }
+    }
+}
+
+pub async fn command_stats(source: String) -> Result<()> {
+    let appdata = AppData::read()?;
+    let network_config = network_config(&appdata)?;
+    let mut stats = Stats::new(network_config.clone());
+
+    if source == "live" {
+        let (_, tcpcan) = connect().await?;
+        cprintln!("<green>Collecting statistics, stop with Ctrl-C</green>");
+        loop {
+            let frame = tokio::select! {
+                frame = tcpcan.recv() => frame,
+                _ = tokio::signal::ctrl_c() => break,
+            };
+            let Some(frame) = frame else {
+                println!("Connection closed");
+                break;
+            };
+            stats.add(&TraceFrame::from_network_frame(&frame));
+        }
+    } else {
+        let trace = read_trace(source.as_ref(), &bus_names(&network_config))?;
+        for frame in &trace.frames {
+            stats.add(frame);
+        }
+    }
+    stats.print();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn period_and_jitter() {
+        let mut stats = MessageStats::default();
+        assert_eq!(stats.period(), None);
+        for timestamp in [0, 10_000, 20_000, 30_000] {
+            stats.add(timestamp, None);
+        }
+        let (period, jitter) = stats.period().unwrap();
+        assert!((period - 0.01).abs() < 1e-9);
+        assert!(jitter < 1e-6);
+
+        let mut stats = MessageStats::default();
+        for timestamp in [0, 8_000, 20_000] {
+            stats.add(timestamp, None);
+        }
+        let (period, jitter) = stats.period().unwrap();
+        assert!((period - 0.01).abs() < 1e-9);
+        assert!((jitter - 0.002).abs() < 1e-9);
+        assert!((stats.min_gap.unwrap() - 0.008).abs() < 1e-9);
+        assert!((stats.max_gap.unwrap() - 0.012).abs() < 1e-9);
+    }
+
+    #[test]
+    fn missed_cycles() {
+        let interval = Some(Duration::from_millis(10));
+        let mut stats = MessageStats::default();
+        // gaps of 1.4 cycles are jitter, 3 cycles miss two frames.
+        for timestamp in [0, 14_000, 44_000, 54_000] {
+            stats.add(timestamp, interval);
+        }
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.missed, 2);
+
+        // messages without a period never miss cycles.
+        let mut stats = MessageStats::default();
+        for timestamp in [0, 1_000_000] {
+            stats.add(timestamp, None);
+        }
+        assert_eq!(stats.missed, 0);
+    }
+}
@@ -11,7 +11,7 @@ use canzero_tcp::tcpcan::TcpCan;
 use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use color_print::cprintln;
 
-use crate::{dump::discover, errors::Result};
+use crate::{config::network_config, dump::discover, errors::Result};
 
 async fn rx_get_req_hash_code(
     tcpcan: Arc<TcpCan>,
@@ -92,7 +92,7 @@ async fn rx_get_req_build_time(
 
 pub async fn command_status() -> Result<()> {
     let appdata = AppData::read()?;
-    let network_config = appdata.config()?;
+    let network_config = network_config(&appdata)?;
     let network_hash = network_config.portable_hash();
 
     let now = Instant::now();
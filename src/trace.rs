@@ -0,0 +1,678 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use canzero_common::TNetworkFrame;
+use canzero_config::config::NetworkRef;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    asc::{read_asc, write_asc},
+    decode::frame_data,
+    errors::{Error, Result},
+    pcap::{read_pcap, PcapWriter, PCAPNG_MAGIC, PCAP_MAGIC, PCAP_MAGIC_NANOS},
+};
+
+const CSV_HEADER: &str = "timestamp; bus; id; ide; rtr; dlc; data";
+/// comment before the csv header with the UTC time of the server timestamp zero.
+const CSV_EPOCH: &str = "# epoch:";
+
+/// a recorded can frame, stored as one json object per line in CANzero traces.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceFrame {
+    /// microseconds since the timebase of the server.
+    pub timestamp: u64,
+    pub bus: u32,
+    pub id: u32,
+    pub ide: bool,
+    pub rtr: bool,
+    pub dlc: u8,
+    pub data: u64,
+}
+
+impl TraceFrame {
+    pub fn from_network_frame(frame: &TNetworkFrame) -> Self {
+        let can_frame = &frame.can_frame;
+        TraceFrame {
+            timestamp: frame.timestamp.as_micros() as u64,
+            bus: frame.bus_id,
+            id: can_frame.get_id(),
+            ide: can_frame.get_ide_flag(),
+            rtr: can_frame.get_rtr_flag(),
+            dlc: can_frame.get_dlc(),
+            data: frame_data(can_frame.get_data_u64(), can_frame.get_dlc()),
+        }
+    }
+
+    pub fn time(&self) -> Duration {
+        Duration::from_micros(self.timestamp)
+    }
+
+    /// the payload of the frame in transmission order.
+    pub fn data_bytes(&self) -> Vec<u8> {
+        self.data.to_le_bytes()[..(self.dlc.min(8) as usize)].to_vec()
+    }
+}
+
+/// the frames of a trace and, if known, the UTC time of the server timestamp zero.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub epoch: Option<DateTime<Utc>>,
+    pub frames: Vec<TraceFrame>,
+}
+
+impl Trace {
+    /// a trace of frames with unix timestamps, the earliest one becomes the epoch.
+    pub fn from_unix(mut frames: Vec<TraceFrame>) -> Self {
+        let Some(start) = frames.iter().map(|frame| frame.timestamp).min() else {
+            return Trace::default();
+        };
+        for frame in &mut frames {
+            frame.timestamp -= start;
+        }
+        Trace {
+            epoch: DateTime::from_timestamp_micros(start as i64),
+            frames,
+        }
+    }
+}
+
+/// first line of CANzero traces, which were recorded with a known clock offset.
+#[derive(Debug, Serialize, Deserialize)]
+struct TraceHeader {
+    /// RFC 3339 UTC time of the server timestamp zero.
+    epoch: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TraceFormat {
+    /// CANzero trace (json lines)
+    Json,
+    /// candump -l log
+    Candump,
+    Csv,
+    /// pcapng with SocketCAN link type, classic pcap is read as well
+    Pcap,
+    /// Vector ASC
+    Asc,
+}
+
+impl TraceFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" | "jsonl" => Some(TraceFormat::Json),
+            "log" => Some(TraceFormat::Candump),
+            "csv" => Some(TraceFormat::Csv),
+            "pcap" | "pcapng" => Some(TraceFormat::Pcap),
+            "asc" => Some(TraceFormat::Asc),
+            _ => None,
+        }
+    }
+
+    /// detects the format of a trace from its content.
+    pub fn detect(content: &[u8]) -> Option<Self> {
+        if content.len() >= 4 {
+            let magic = u32::from_le_bytes(content[..4].try_into().unwrap());
+            if magic == PCAPNG_MAGIC || magic == PCAP_MAGIC || magic == PCAP_MAGIC_NANOS {
+                return Some(TraceFormat::Pcap);
+            }
+        }
+        let text = std::str::from_utf8(content).ok()?;
+        let first_line = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+        if first_line.starts_with('{') {
+            Some(TraceFormat::Json)
+        } else if first_line.starts_with('(') {
+            Some(TraceFormat::Candump)
+        } else if first_line.replace(' ', "") == CSV_HEADER.replace(' ', "") {
+            Some(TraceFormat::Csv)
+        } else if first_line.starts_with("date")
+            || first_line.starts_with("base")
+            || text.contains("Begin Triggerblock")
+        {
+            Some(TraceFormat::Asc)
+        } else {
+            None
+        }
+    }
+}
+
+/// ids and names of the buses of the network config,
+/// used to map the interfaces of traces to buses.
+pub fn bus_names(network: &NetworkRef) -> Vec<(u32, String)> {
+    network
+        .buses()
+        .iter()
+        .map(|bus| (bus.id(), bus.name().to_owned()))
+        .collect()
+}
+
+/// the bus of an interface of a trace, either by bus name or by the number of the interface (e.g. can1).
+pub fn interface_bus(buses: &[(u32, String)], interface: &str) -> Result<u32> {
+    if let Some((bus_id, _)) = buses.iter().find(|(_, name)| name == interface) {
+        return Ok(*bus_id);
+    }
+    let digits = interface.len()
+        - interface
+            .chars()
+            .rev()
+            .take_while(char::is_ascii_digit)
+            .count();
+    interface[digits..].parse().map_err(|_| {
+        Error::InvalidTraceFile(format!(
+            "interface {interface} is neither a bus of the network config nor numbered"
+        ))
+    })
+}
+
+pub fn bus_interface(buses: &[(u32, String)], bus_id: u32) -> String {
+    match buses.iter().find(|(id, _)| *id == bus_id) {
+        Some((_, name)) => name.clone(),
+        None => format!("can{bus_id}"),
+    }
+}
+
+fn invalid_line(format: &str, line_number: usize, line: &str) -> Error {
+    Error::InvalidTraceFile(format!("line {} of {format} : {line}", line_number + 1))
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.is_ascii() || hex.len() % 2 != 0 || hex.len() > 16 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn data_from_bytes(bytes: &[u8]) -> u64 {
+    let mut data = [0; 8];
+    data[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(data)
+}
+
+fn hex_bytes(frame: &TraceFrame) -> String {
+    frame
+        .data_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect()
+}
+
+fn read_json(text: &str) -> Result<Trace> {
+    let mut trace = Trace::default();
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if trace.frames.is_empty() && trace.epoch.is_none() {
+            if let Ok(header) = serde_json::from_str::<TraceHeader>(line) {
+                match DateTime::parse_from_rfc3339(&header.epoch) {
+                    Ok(epoch) => trace.epoch = Some(epoch.with_timezone(&Utc)),
+                    Err(_) => return Err(invalid_line("json trace", line_number, line)),
+                }
+                continue;
+            }
+        }
+        match serde_json::from_str(line) {
+            Ok(frame) => trace.frames.push(frame),
+            Err(_) => return Err(invalid_line("json trace", line_number, line)),
+        }
+    }
+    Ok(trace)
+}
+
+/// parses a line of candump -l, e.g. (1436509052.249713) can0 12345678#DEADBEEF,
+/// fails if the interface isn't a bus.
+fn parse_candump_line(buses: &[(u32, String)], line: &str) -> Option<Result<TraceFrame>> {
+    let mut parts = line.split_whitespace();
+    let timestamp = parts.next()?.strip_prefix('(')?.strip_suffix(')')?;
+    let interface = parts.next()?;
+    let (id, data) = parts.next()?.split_once('#')?;
+
+    let (secs, micros) = timestamp.split_once('.').unwrap_or((timestamp, "0"));
+    let micros = format!("{micros:0<6}");
+    let timestamp = secs.parse::<u64>().ok()? * 1_000_000 + micros[..6].parse::<u64>().ok()?;
+
+    let (rtr, dlc, data) = match data.strip_prefix('R') {
+        Some(dlc) => (true, dlc.parse().unwrap_or(0), 0),
+        None => {
+            let bytes = parse_hex_bytes(data)?;
+            (false, bytes.len() as u8, data_from_bytes(&bytes))
+        }
+    };
+    let ide = id.len() == 8;
+    let id = u32::from_str_radix(id, 16).ok()?;
+    Some(interface_bus(buses, interface).map(|bus| TraceFrame {
+        timestamp,
+        bus,
+        id,
+        // candump prints extended ids with 8 digits
+        ide,
+        rtr,
+        dlc,
+        data,
+    }))
+}
+
+fn read_candump(text: &str, buses: &[(u32, String)]) -> Result<Trace> {
+    let mut frames = vec![];
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_candump_line(buses, line) {
+            Some(frame) => frames.push(frame?),
+            None => return Err(invalid_line("candump log", line_number, line)),
+        }
+    }
+    Ok(Trace::from_unix(frames))
+}
+
+/// without an epoch, the timestamps are relative to the server timebase.
+fn write_candump(trace: &Trace, buses: &[(u32, String)]) -> String {
+    let start = trace
+        .epoch
+        .map_or(0, |epoch| epoch.timestamp_micros().max(0) as u64);
+    let mut out = String::new();
+    for frame in &trace.frames {
+        let timestamp = start + frame.timestamp;
+        let id = if frame.ide {
+            format!("{:08X}", frame.id)
+        } else {
+            format!("{:03X}", frame.id)
+        };
+        let data = if frame.rtr {
+            format!("R{}", frame.dlc)
+        } else {
+            hex_bytes(frame)
+        };
+        out.push_str(&format!(
+            "({}.{:06}) {} {id}#{data}\n",
+            timestamp / 1_000_000,
+            timestamp % 1_000_000,
+            bus_interface(buses, frame.bus)
+        ));
+    }
+    out
+}
+
+fn parse_csv_line(line: &str) -> Option<TraceFrame> {
+    let values: Vec<&str> = line.split(';').map(str::trim).collect();
+    let [timestamp, bus, id, ide, rtr, dlc, data] = values[..] else {
+        return None;
+    };
+    let bytes = parse_hex_bytes(data)?;
+    Some(TraceFrame {
+        timestamp: timestamp.parse().ok()?,
+        bus: bus.parse().ok()?,
+        id: u32::from_str_radix(id.trim_start_matches("0x"), 16).ok()?,
+        ide: ide.parse().ok()?,
+        rtr: rtr.parse().ok()?,
+        dlc: dlc.parse().ok()?,
+        data: data_from_bytes(&bytes),
+    })
+}
+
+fn read_csv(text: &str) -> Result<Trace> {
+    let mut trace = Trace::default();
+    let mut header = true;
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('#') {
+            if let Some(epoch) = line.strip_prefix(CSV_EPOCH) {
+                match DateTime::parse_from_rfc3339(epoch.trim()) {
+                    Ok(epoch) => trace.epoch = Some(epoch.with_timezone(&Utc)),
+                    Err(_) => return Err(invalid_line("csv trace", line_number, line)),
+                }
+            }
+            continue;
+        }
+        if header {
+            header = false;
+            continue;
+        }
+        match parse_csv_line(line) {
+            Some(frame) => trace.frames.push(frame),
+            None => return Err(invalid_line("csv trace", line_number, line)),
+        }
+    }
+    Ok(trace)
+}
+
+fn write_csv(trace: &Trace) -> String {
+    let mut out = String::new();
+    if let Some(epoch) = trace.epoch {
+        out.push_str(&format!(
+            "{CSV_EPOCH} {}\n",
+            epoch.to_rfc3339_opts(SecondsFormat::Micros, true)
+        ));
+    }
+    out.push_str(&format!("{CSV_HEADER}\n"));
+    for frame in &trace.frames {
+        out.push_str(&format!(
+            "{}; {}; 0x{:X}; {}; {}; {}; {}\n",
+            frame.timestamp,
+            frame.bus,
+            frame.id,
+            frame.ide,
+            frame.rtr,
+            frame.dlc,
+            hex_bytes(frame)
+        ));
+    }
+    out
+}
+
+/// reads a trace in any of the supported formats, the format is detected from the content.
+/// the clock offset of the server is read from all formats but ASC.
+pub fn read_trace(path: &Path, buses: &[(u32, String)]) -> Result<Trace> {
+    let Ok(content) = std::fs::read(path) else {
+        return Err(Error::FileNotFound(format!("{path:?}")));
+    };
+    let Some(format) = TraceFormat::detect(&content) else {
+        return Err(Error::UnsupportedFileFormat(format!("{path:?}")));
+    };
+    let text = || String::from_utf8_lossy(&content);
+    let frames = |frames: Result<Vec<TraceFrame>>| {
+        frames.map(|frames| Trace {
+            epoch: None,
+            frames,
+        })
+    };
+    let trace = match format {
+        TraceFormat::Json => read_json(&text()),
+        TraceFormat::Candump => read_candump(&text(), buses),
+        TraceFormat::Csv => read_csv(&text()),
+        TraceFormat::Pcap => read_pcap(&content, buses),
+        TraceFormat::Asc => frames(read_asc(&text())),
+    };
+    trace.map_err(|err| match err {
+        Error::InvalidTraceFile(err) => Error::InvalidTraceFile(format!("{path:?} {err}")),
+        err => err,
+    })
+}
+
+pub fn write_trace(
+    path: &Path,
+    format: TraceFormat,
+    trace: &Trace,
+    buses: &[(u32, String)],
+) -> Result<()> {
+    let frames = &trace.frames;
+    match format {
+        TraceFormat::Json => {
+            let mut writer = TraceWriter::create(path, trace.epoch)?;
+            for frame in frames {
+                writer.write(frame)?;
+            }
+            writer.flush()?;
+        }
+        TraceFormat::Candump => std::fs::write(path, write_candump(trace, buses))?,
+        TraceFormat::Csv => std::fs::write(path, write_csv(trace))?,
+        TraceFormat::Pcap => {
+            let mut writer = PcapWriter::create(path, buses, trace.epoch)?;
+            for frame in frames {
+                writer.write(frame)?;
+            }
+            writer.flush()?;
+        }
+        TraceFormat::Asc => {
+            // without a clock offset, the log starts at the unix epoch.
+            let start = trace.epoch.unwrap_or(DateTime::<Utc>::UNIX_EPOCH);
+            std::fs::write(path, write_asc(frames, start))?
+        }
+    }
+    Ok(())
+}
+
+/// writes a CANzero trace (json lines).
+pub struct TraceWriter {
+    writer: BufWriter<File>,
+}
+
+impl TraceWriter {
+    /// the epoch, if known, is stored in a header line,
+    /// such that recorded traces can be mapped to UTC.
+    pub fn create(path: &Path, epoch: Option<DateTime<Utc>>) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if let Some(epoch) = epoch {
+            let header = TraceHeader {
+                epoch: epoch.to_rfc3339_opts(SecondsFormat::Micros, true),
+            };
+            serde_json::to_writer(&mut writer, &header).map_err(std::io::Error::from)?;
+            writeln!(writer)?;
+        }
+        Ok(TraceWriter { writer })
+    }
+
+    pub fn write(&mut self, frame: &TraceFrame) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, frame).map_err(std::io::Error::from)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn sample_buses() -> Vec<(u32, String)> {
+        vec![(0, "can0".to_owned()), (1, "can1".to_owned())]
+    }
+
+    /// standard, extended and remote frames, shared by the tests of all trace formats.
+    pub(crate) fn sample_frames() -> Vec<TraceFrame> {
+        vec![
+            TraceFrame {
+                timestamp: 12,
+                bus: 0,
+                id: 0x123,
+                ide: false,
+                rtr: false,
+                dlc: 2,
+                data: 0xBEEF,
+            },
+            TraceFrame {
+                timestamp: 1_234_567,
+                bus: 1,
+                id: 0x1ABCDEF,
+                ide: true,
+                rtr: false,
+                dlc: 8,
+                data: 0x0807060504030201,
+            },
+            TraceFrame {
+                timestamp: 2_000_000,
+                bus: 1,
+                id: 0x42,
+                ide: false,
+                rtr: true,
+                dlc: 0,
+                data: 0,
+            },
+            // beyond 32 bit microseconds, on a bus which is not part of the config.
+            TraceFrame {
+                timestamp: 5_000_000_123,
+                bus: 5,
+                id: 0x10,
+                ide: false,
+                rtr: false,
+                dlc: 1,
+                data: 0xAA,
+            },
+        ]
+    }
+
+    /// the UTC times and the frames without timestamps,
+    /// which are equal for traces with different epochs.
+    pub(crate) fn utc_frames(trace: &Trace) -> Vec<(DateTime<Utc>, TraceFrame)> {
+        let epoch = trace.epoch.unwrap_or(DateTime::<Utc>::UNIX_EPOCH);
+        trace
+            .frames
+            .iter()
+            .map(|frame| {
+                let time = epoch + chrono::Duration::microseconds(frame.timestamp as i64);
+                (
+                    time,
+                    TraceFrame {
+                        timestamp: 0,
+                        ..frame.clone()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub(crate) fn sample_trace() -> Trace {
+        Trace {
+            epoch: Some(
+                DateTime::parse_from_rfc3339("2024-06-01T12:00:00.5Z")
+                    .unwrap()
+                    .into(),
+            ),
+            frames: sample_frames(),
+        }
+    }
+
+    fn round_trip(format: TraceFormat, extension: &str) -> Trace {
+        let path = std::env::temp_dir().join(format!(
+            "canzero-trace-test-{}.{extension}",
+            std::process::id()
+        ));
+        write_trace(&path, format, &sample_trace(), &sample_buses()).unwrap();
+        let content = std::fs::read(&path).unwrap();
+        assert_eq!(TraceFormat::detect(&content), Some(format));
+        let read = read_trace(&path, &sample_buses());
+        std::fs::remove_file(&path).unwrap();
+        read.unwrap()
+    }
+
+    #[test]
+    fn json_round_trip_keeps_the_epoch() {
+        let read = round_trip(TraceFormat::Json, "json");
+        assert_eq!(read.epoch, sample_trace().epoch);
+        assert_eq!(read.frames, sample_frames());
+    }
+
+    #[test]
+    fn json_without_header() {
+        let line = serde_json::to_string(&sample_frames()[0]).unwrap();
+        let read = read_json(&format!("{line}\n\n{line}\n")).unwrap();
+        assert_eq!(read.epoch, None);
+        assert_eq!(read.frames.len(), 2);
+        assert!(read_json("{\"timestamp\": 1}").is_err());
+    }
+
+    #[test]
+    fn candump_round_trip_keeps_utc_times() {
+        let read = round_trip(TraceFormat::Candump, "log");
+        // the first frame starts the read trace.
+        assert_eq!(read.frames[0].timestamp, 0);
+        assert_eq!(utc_frames(&read), utc_frames(&sample_trace()));
+    }
+
+    #[test]
+    fn candump_without_epoch() {
+        let trace = Trace {
+            epoch: None,
+            frames: sample_frames(),
+        };
+        let log = write_candump(&trace, &sample_buses());
+        assert!(log.starts_with("(0.000012) can0 123#EFBE\n"));
+        let read = read_candump(&log, &sample_buses()).unwrap();
+        assert_eq!(read.epoch, DateTime::from_timestamp_micros(12));
+        assert!(read_candump("", &sample_buses()).unwrap().frames.is_empty());
+    }
+
+    #[test]
+    fn csv_round_trip_keeps_the_epoch() {
+        let read = round_trip(TraceFormat::Csv, "csv");
+        assert_eq!(read.epoch, sample_trace().epoch);
+        assert_eq!(read.frames, sample_frames());
+        let read = read_csv(&write_csv(&Trace {
+            epoch: None,
+            frames: sample_frames(),
+        }))
+        .unwrap();
+        assert_eq!(read.epoch, None);
+        assert_eq!(read.frames, sample_frames());
+    }
+
+    #[test]
+    fn pcap_round_trip_keeps_utc_times() {
+        let read = round_trip(TraceFormat::Pcap, "pcapng");
+        assert_eq!(utc_frames(&read), utc_frames(&sample_trace()));
+    }
+
+    #[test]
+    fn asc_round_trip() {
+        assert_eq!(round_trip(TraceFormat::Asc, "asc").frames, sample_frames());
+    }
+
+    #[test]
+    fn parses_candump_lines() {
+        let frame = parse_candump_line(
+            &sample_buses(),
+            "(1436509052.249713) can1 12345678#DEADBEEF",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(frame.timestamp, 1_436_509_052_249_713);
+        assert_eq!(frame.bus, 1);
+        assert_eq!(frame.id, 0x12345678);
+        assert!(frame.ide);
+        assert_eq!(frame.dlc, 4);
+        assert_eq!(frame.data_bytes(), [0xDE, 0xAD, 0xBE, 0xEF]);
+        // fractions with less than 6 digits
+        let frame = parse_candump_line(&sample_buses(), "(1.5) vcan3 123#")
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.timestamp, 1_500_000);
+        assert_eq!(frame.bus, 3);
+        assert_eq!(frame.dlc, 0);
+        assert!(parse_candump_line(&sample_buses(), "(1.5) can0 123#ABC").is_none());
+        assert!(parse_candump_line(&sample_buses(), "(1.5) vcan_x 123#")
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn maps_interfaces_to_buses() {
+        assert_eq!(interface_bus(&sample_buses(), "can1").unwrap(), 1);
+        assert_eq!(interface_bus(&[(4, "main".to_owned())], "main").unwrap(), 4);
+        assert_eq!(interface_bus(&sample_buses(), "vcan7").unwrap(), 7);
+        assert!(interface_bus(&sample_buses(), "vcan_x").is_err());
+        assert_eq!(bus_interface(&sample_buses(), 1), "can1");
+        assert_eq!(bus_interface(&sample_buses(), 9), "can9");
+    }
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(
+            TraceFormat::from_path(Path::new("a.jsonl")),
+            Some(TraceFormat::Json)
+        );
+        assert_eq!(TraceFormat::from_path(Path::new("a.txt")), None);
+        assert_eq!(
+            TraceFormat::detect(b"date Mon Jan 01\n"),
+            Some(TraceFormat::Asc)
+        );
+        assert_eq!(TraceFormat::detect(b"hello"), None);
+    }
+}
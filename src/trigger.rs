@@ -0,0 +1,279 @@
+use std::{collections::VecDeque, path::PathBuf, time::Duration};
+
+use canzero_config::config::NetworkRef;
+use chrono::{DateTime, Utc};
+use color_print::cprintln;
+
+use crate::{
+    errors::Result,
+    expr::{Expr, FrameContext},
+    trace::{bus_names, write_trace, Trace, TraceFormat, TraceFrame},
+};
+
+/// keeps the frames of the last pre seconds in a ring buffer and collects them,
+/// together with the frames of the following post seconds, for every frame on which
+/// the condition becomes true. Frames without a variable of the condition don't change it,
+/// so a signal condition fires when its message changes the value, an id condition
+/// fires for every frame of the message between other frames.
+struct Window {
+    pre: Duration,
+    post: Duration,
+    buffer: VecDeque<TraceFrame>,
+    /// timestamps of the trigger events, whose windows aren't complete yet.
+    pending: VecDeque<u64>,
+    /// value of the condition on the last frame, which defined it.
+    matched: bool,
+}
+
+impl Window {
+    fn new(pre: Duration, post: Duration) -> Self {
+        Window {
+            pre,
+            post,
+            buffer: VecDeque::new(),
+            pending: VecDeque::new(),
+            matched: false,
+        }
+    }
+
+    /// adds a frame with the value of the condition and returns true if it fired the trigger.
+    fn add(&mut self, frame: &TraceFrame, condition: Option<bool>) -> bool {
+        let fires = condition == Some(true) && !self.matched;
+        if let Some(condition) = condition {
+            self.matched = condition;
+        }
+        if fires {
+            self.pending.push_back(frame.timestamp);
+        }
+        self.buffer.push_back(frame.clone());
+        // the frames of the oldest open window are kept.
+        let start = self
+            .pending
+            .front()
+            .copied()
+            .unwrap_or(frame.timestamp)
+            .saturating_sub(self.pre.as_micros() as u64);
+        while self
+            .buffer
+            .front()
+            .is_some_and(|frame| frame.timestamp < start)
+        {
+            self.buffer.pop_front();
+        }
+        fires
+    }
+
+    /// takes the trigger timestamp and frames of the oldest window, which is complete at timestamp,
+    /// or, without a timestamp, of any pending window.
+    fn take(&mut self, timestamp: Option<u64>) -> Option<(u64, Vec<TraceFrame>)> {
+        let fired = *self.pending.front()?;
+        let end = fired + self.post.as_micros() as u64;
+        if timestamp.is_some_and(|timestamp| timestamp < end) {
+            return None;
+        }
+        self.pending.pop_front();
+        let start = fired.saturating_sub(self.pre.as_micros() as u64);
+        let frames = self
+            .buffer
+            .iter()
+            .filter(|frame| (start..=end).contains(&frame.timestamp))
+            .cloned()
+            .collect();
+        Some((fired, frames))
+    }
+}
+
+/// saves the frames around every trigger event of an expression.
+pub struct Trigger {
+    network_config: NetworkRef,
+    expr: Expr,
+    dir: PathBuf,
+    /// UTC time of the server timestamp zero, stored in the saved traces.
+    epoch: Option<DateTime<Utc>>,
+    /// UTC start of the capture, prefixed to the file names to keep captures of different runs apart.
+    run: String,
+    window: Window,
+}
+
+impl Trigger {
+    pub fn new(
+        network_config: NetworkRef,
+        expr: Expr,
+        pre: Duration,
+        post: Duration,
+        dir: PathBuf,
+        epoch: Option<DateTime<Utc>>,
+    ) -> Self {
+        Trigger {
+            network_config,
+            expr,
+            dir,
+            epoch,
+            run: Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+            window: Window::new(pre, post),
+        }
+    }
+
+    pub fn add(&mut self, frame: &TraceFrame) -> Result<()> {
+        let condition = FrameContext::new(&self.network_config, frame).condition(&self.expr);
+        if self.window.add(frame, condition) {
+            cprintln!(
+                "<yellow>Triggered at {:.3}s</yellow>",
+                frame.time().as_secs_f64()
+            );
+        }
+        while let Some((fired, frames)) = self.window.take(Some(frame.timestamp)) {
+            self.save(fired, frames)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, fired: u64, frames: Vec<TraceFrame>) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let name = format!("trigger-{}-{:.3}s", self.run, fired as f64 * 1e-6);
+        let mut path = self.dir.join(format!("{name}.json"));
+        // never overwrite earlier captures.
+        let mut n = 1;
+        while path.exists() {
+            path = self.dir.join(format!("{name}-{n}.json"));
+            n += 1;
+        }
+        let trace = Trace {
+            epoch: self.epoch,
+            frames,
+        };
+        write_trace(
+            &path,
+            TraceFormat::Json,
+            &trace,
+            &bus_names(&self.network_config),
+        )?;
+        cprintln!(
+            "<green>Saved {} frames around the trigger to {path:?}</green>",
+            trace.frames.len()
+        );
+        Ok(())
+    }
+
+    /// saves the windows of triggers, which fired less than post seconds ago.
+    pub fn finish(&mut self) -> Result<()> {
+        while let Some((fired, frames)) = self.window.take(None) {
+            self.save(fired, frames)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(timestamp_ms: u64, id: u32) -> TraceFrame {
+        TraceFrame {
+            timestamp: timestamp_ms * 1000,
+            bus: 0,
+            id,
+            ide: false,
+            rtr: false,
+            dlc: 0,
+            data: 0,
+        }
+    }
+
+    fn timestamps_ms(frames: &[TraceFrame]) -> Vec<u64> {
+        frames.iter().map(|frame| frame.timestamp / 1000).collect()
+    }
+
+    #[test]
+    fn saves_pre_and_post_frames() {
+        let mut window = Window::new(Duration::from_millis(20), Duration::from_millis(20));
+        for t in [0, 10, 20, 30] {
+            assert!(!window.add(&frame(t, 1), Some(false)));
+            assert_eq!(window.take(Some(t * 1000)), None);
+        }
+        assert!(window.add(&frame(40, 1), Some(true)));
+        assert_eq!(window.take(Some(40_000)), None);
+        window.add(&frame(50, 1), Some(true));
+        assert_eq!(window.take(Some(50_000)), None);
+        window.add(&frame(60, 1), Some(true));
+        let (fired, frames) = window.take(Some(60_000)).unwrap();
+        assert_eq!(fired, 40_000);
+        assert_eq!(timestamps_ms(&frames), [20, 30, 40, 50, 60]);
+    }
+
+    #[test]
+    fn fires_on_rising_edges() {
+        let mut window = Window::new(Duration::ZERO, Duration::ZERO);
+        let fired: Vec<bool> = [true, true, false, true]
+            .iter()
+            .enumerate()
+            .map(|(t, matched)| {
+                let fires = window.add(&frame(t as u64, 1), Some(*matched));
+                window.take(Some(t as u64 * 1000));
+                fires
+            })
+            .collect();
+        assert_eq!(fired, [true, false, false, true]);
+    }
+
+    #[test]
+    fn frames_without_the_condition_keep_it() {
+        let mut window = Window::new(Duration::ZERO, Duration::ZERO);
+        assert!(window.add(&frame(0, 1), Some(true)));
+        window.take(Some(0));
+        assert!(!window.add(&frame(1, 2), None));
+        assert!(!window.add(&frame(2, 1), Some(true)));
+    }
+
+    #[test]
+    fn periodic_id_triggers_fire_every_time() {
+        // id=1 with another message in between, every 5ms.
+        let mut window = Window::new(Duration::from_millis(5), Duration::from_millis(5));
+        let mut windows = vec![];
+        for t in (0..40).step_by(5) {
+            let id = if t % 10 == 0 { 1 } else { 2 };
+            assert_eq!(window.add(&frame(t, id), Some(id == 1)), id == 1);
+            while let Some((fired, frames)) = window.take(Some(t * 1000)) {
+                windows.push((fired / 1000, timestamps_ms(&frames)));
+            }
+        }
+        assert_eq!(
+            windows,
+            [
+                (0, vec![0, 5]),
+                (10, vec![5, 10, 15]),
+                (20, vec![15, 20, 25]),
+                (30, vec![25, 30, 35]),
+            ]
+        );
+        assert_eq!(window.take(None), None);
+    }
+
+    #[test]
+    fn edges_in_open_windows_start_their_own_window() {
+        let mut window = Window::new(Duration::from_millis(10), Duration::from_millis(30));
+        assert!(window.add(&frame(0, 1), Some(true)));
+        window.add(&frame(10, 1), Some(false));
+        assert!(window.add(&frame(20, 1), Some(true)));
+        window.add(&frame(30, 1), Some(true));
+        let (fired, frames) = window.take(Some(30_000)).unwrap();
+        assert_eq!(fired, 0);
+        assert_eq!(timestamps_ms(&frames), [0, 10, 20, 30]);
+        assert_eq!(window.take(Some(30_000)), None);
+        let (fired, frames) = window.take(None).unwrap();
+        assert_eq!(fired, 20_000);
+        assert_eq!(timestamps_ms(&frames), [10, 20, 30]);
+        assert_eq!(window.take(None), None);
+    }
+
+    #[test]
+    fn finish_takes_pending_windows() {
+        let mut window = Window::new(Duration::from_secs(1), Duration::from_secs(1));
+        window.add(&frame(0, 1), Some(true));
+        window.add(&frame(10, 1), Some(true));
+        let (fired, frames) = window.take(None).unwrap();
+        assert_eq!(fired, 0);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(window.take(None), None);
+    }
+}
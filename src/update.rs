@@ -3,6 +3,7 @@ use std::{net::IpAddr, str::FromStr};
 use canzero_appdata::AppData;
 
 use crate::{
+    config::{config_dir, config_path},
     errors::{Error, Result},
     ssh::scan_ssh,
 };
@@ -20,7 +21,7 @@ pub async fn command_update_server(
     build: bool,
 ) -> Result<()> {
     let appdata = AppData::read()?;
-    let Some(config_path) = appdata.get_config_path() else {
+    let Some(config_path) = config_path(&appdata) else {
         return Err(Error::NoConfigSelected);
     };
 
@@ -114,10 +115,7 @@ $ rustup target add {PI_ARCH}"
             };
             nd.server_addr
         };
-        let mut config_files = appdata.config_files()?;
-        config_files.push(config_path.clone());
-        let config_dir = common_path::common_path_all(config_files.iter().map(|p| p.as_path()))
-            .expect("Failed to find common network-config directory");
+        let config_dir = config_dir(&appdata)?;
 
         // assumes that the main config file is the the common directory
         let relative_config_path = config_path.file_name().unwrap().to_str().unwrap();